@@ -3,4 +3,4 @@
 cd "$(dirname "$0")" || return
 
 cargo build --release
-../target/release/poe_query -v -p "/home/nihil/Games/path-of-exile/drive_c/Program Files (x86)/Grinding Gear Games/Path of Exile/" "$(< mods.pql)"
+../target/release/poe_query -v -f mods.pql
@@ -0,0 +1,96 @@
+use std::io;
+use std::path::Path;
+
+use log::*;
+use poe_bundle::BundleReaderRead;
+
+use crate::dds;
+use crate::traversal::value::Value;
+
+/** Paths in the bundle index matching any of the glob patterns, all of them without patterns */
+pub fn matching<'p>(paths: &'p [String], patterns: &[String]) -> Vec<&'p str> {
+    let patterns: Vec<Vec<char>> = patterns.iter().map(|pattern| pattern.to_lowercase().chars().collect()).collect();
+    let mut matching: Vec<&str> = paths.iter()
+        .map(String::as_str)
+        .filter(|path| {
+            let path: Vec<char> = path.chars().collect();
+            patterns.is_empty() || patterns.iter().any(|pattern| glob(pattern, &path))
+        })
+        .collect();
+    matching.sort();
+    matching
+}
+
+// `*` and `?` stay within a folder, `**` crosses folders and `**/` can match no folder at all
+fn glob(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            let skipped_folders = rest.first() == Some(&'/') && glob(&rest[1..], path);
+            skipped_folders || (0..=path.len()).any(|i| glob(rest, &path[i..]))
+        }
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|i| !path[..*i].contains(&'/'))
+            .any(|i| glob(rest, &path[i..])),
+        ['?', rest @ ..] => matches!(path, [c, ..] if *c != '/') && glob(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && glob(rest, &path[1..]),
+    }
+}
+
+/** Writes the files below `output`, keeping their path from the bundles */
+pub fn extract(bundles: &impl BundleReaderRead, paths: &[&str], output: &Path) -> io::Result<()> {
+    for path in paths {
+        let bytes = bundles.bytes(path)
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path, error)))?;
+        let destination = output.join(path);
+        if let Some(directory) = destination.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&destination, bytes)?;
+        debug!("Extracted {}", path);
+    }
+    Ok(())
+}
+
+/** Writes every string in the value that is a file in the bundles below `output`, textures are decoded to PNG */
+pub fn extract_referenced(bundles: &impl BundleReaderRead, value: &Value, output: &Path) -> io::Result<usize> {
+    let mut paths = Vec::new();
+    referenced(bundles, value, &mut paths);
+    paths.sort();
+    paths.dedup();
+
+    let read = |path: &str| bundles.size_of(path).and_then(|_| bundles.bytes(path).ok());
+    for path in &paths {
+        let (destination, bytes) = match path.ends_with(".dds") {
+            true => match dds::read(&read, path).and_then(|image| dds::png(&image)) {
+                Ok(png) => (output.join(path).with_extension("png"), png),
+                Err(error) => {
+                    warn!("Failed to decode {}, writing it as is: {}", path, error);
+                    (output.join(path), read(path).unwrap_or_default())
+                }
+            },
+            false => (output.join(path), read(path).unwrap_or_default()),
+        };
+        if let Some(directory) = destination.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&destination, bytes)?;
+        debug!("Extracted {}", path);
+    }
+    Ok(paths.len())
+}
+
+// keys of objects are never paths
+fn referenced(bundles: &impl BundleReaderRead, value: &Value, paths: &mut Vec<String>) {
+    match value {
+        Value::Str(path) => {
+            let path = path.to_lowercase();
+            if bundles.size_of(&path).is_some() {
+                paths.push(path);
+            }
+        }
+        Value::List(values) | Value::Iterator(values) => values.iter().for_each(|value| referenced(bundles, value, paths)),
+        Value::KeyValue(_, value) | Value::Object(value) => referenced(bundles, value, paths),
+        _ => {}
+    }
+}
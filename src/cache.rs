@@ -0,0 +1,113 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+
+use log::*;
+use poe_bundle::BundleReader;
+
+const PATHS: &str = "paths";
+
+/** Bundles of an install read through a cache directory, they are only indexed when a file isn't cached yet */
+pub struct CachedBundles {
+    install_path: PathBuf,
+    /** Folder for the current version of the game, older ones of the same install are removed */
+    directory: PathBuf,
+    /** Every path in the bundle index, sorted */
+    paths: Vec<String>,
+    bundles: OnceLock<BundleReader>,
+}
+
+impl CachedBundles {
+    pub fn open(install_path: &Path, cache: &Path) -> io::Result<CachedBundles> {
+        let version = version(install_path)?;
+        remove_outdated(cache, install_path, &version)?;
+        let directory = cache.join(version);
+        let bundles = OnceLock::new();
+
+        let paths = match std::fs::read_to_string(directory.join(PATHS)) {
+            Ok(paths) => paths.lines().map(String::from).collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                let index = &bundles.get_or_init(|| BundleReader::from_install(install_path)).index;
+                let mut paths = index.paths.clone();
+                paths.sort();
+                write_replacing(&directory.join(PATHS), paths.join("\n").as_bytes())?;
+                paths
+            }
+            Err(error) => return Err(error),
+        };
+        Ok(CachedBundles { install_path: install_path.to_path_buf(), directory, paths, bundles })
+    }
+
+    /** Indexes the bundles the first time they are needed */
+    pub fn bundles(&self) -> &BundleReader {
+        self.bundles.get_or_init(|| {
+            info!("Indexing the bundles, {} is missing a file", self.directory.display());
+            BundleReader::from_install(&self.install_path)
+        })
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.paths.binary_search_by(|known| known.as_str().cmp(path)).is_ok()
+    }
+
+    /** Decompressed files by their lowercase path */
+    pub fn files(&self) -> PathBuf {
+        self.directory.join("files")
+    }
+}
+
+/** Changes whenever the game patches, from the size and modification time of the bundle index, prefixed by the install */
+pub fn version(install_path: &Path) -> io::Result<String> {
+    let index = match install_path.join("Bundles2/_.index.bin") {
+        index if index.exists() => index,
+        // the index is inside the GGPK, which is rewritten by the patch
+        _ if install_path.is_file() => install_path.to_path_buf(),
+        _ => install_path.join("Content.ggpk"),
+    };
+    let metadata = std::fs::metadata(index)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(format!("{}{}-{}", install_prefix(install_path), metadata.len(), modified.as_nanos()))
+}
+
+// several installs can share a cache directory, e.g. both games, so each one is told apart by a hash of its path
+fn install_prefix(install_path: &Path) -> String {
+    let path = install_path.canonicalize().unwrap_or_else(|_| install_path.to_path_buf());
+    // FNV-1a, unlike the hasher of the standard library it is the same in every build
+    let hash = path.to_string_lossy().bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    format!("index-{:016x}-", hash)
+}
+
+// only folders the cache created itself for this install are removed, the directory may be shared with other files
+fn remove_outdated(cache: &Path, install_path: &Path, version: &str) -> io::Result<()> {
+    let Ok(entries) = std::fs::read_dir(cache) else { return Ok(()) };
+    let prefix = install_prefix(install_path);
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(&prefix) && name != version && entry.path().is_dir() {
+            info!("Removing the cache of an earlier version {}", entry.path().display());
+            std::fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/** Writes next to `path` first so readers never see a partial file */
+pub(crate) fn write_replacing(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // unique per write, threads and processes may cache the same file at once
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(format!(".{}-{}.partial", std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed)));
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)
+}
@@ -0,0 +1,53 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/** Settings that are used when the matching flag isn't given */
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    pub install_path: Option<PathBuf>,
+    pub language: Option<String>,
+    pub schema_path: Option<PathBuf>,
+    pub cache_path: Option<PathBuf>,
+}
+
+impl Config {
+    /** `poe_query/config.toml` in the user's config directory, following XDG on Linux */
+    pub fn default_path() -> Option<PathBuf> {
+        let directory = match env::var_os("XDG_CONFIG_HOME").filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None if cfg!(windows) => PathBuf::from(env::var_os("APPDATA")?),
+            None if cfg!(target_os = "macos") => PathBuf::from(env::var_os("HOME")?).join("Library/Application Support"),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(directory.join("poe_query").join("config.toml"))
+    }
+
+    /** A missing file is an empty config */
+    pub fn read(path: &Path) -> Result<Config, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|error| format!("Invalid config {}: {}", path.display(), error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(error) => Err(format!("Failed to read {}: {}", path.display(), error)),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|error| error.to_string())?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|error| format!("Failed to create {}: {}", directory.display(), error))?;
+        }
+        std::fs::write(path, text).map_err(|error| format!("Failed to write {}: {}", path.display(), error))
+    }
+
+    /** Values set in `other` replace the ones in this config */
+    pub fn merge(self, other: Config) -> Config {
+        Config {
+            install_path: other.install_path.or(self.install_path),
+            language: other.language.or(self.language),
+            schema_path: other.schema_path.or(self.schema_path),
+            cache_path: other.cache_path.or(self.cache_path),
+        }
+    }
+}
@@ -3,7 +3,10 @@ use core::panic;
 use std::fmt::Error;
 use log::*;
 use std::io::Cursor;
+use std::ops::Deref;
 use std::process;
+use std::sync::Arc;
+use memmap2::Mmap;
 use crate::traversal::value::Value;
 use crate::traversal::value::Value::U64;
 
@@ -13,9 +16,35 @@ use super::util;
 
 const DATA_SECTION_MARKER: &[u8; 8] = &[0xBB; 8];
 
+/** Contents of a file, copies of a DatFile share them */
+#[derive(Clone)]
+pub enum FileBytes {
+    Memory(Arc<[u8]>),
+    /** Decompressed earlier and mapped from the file cache */
+    Mapped(Arc<Mmap>),
+}
+
+impl Deref for FileBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileBytes::Memory(bytes) => bytes,
+            FileBytes::Mapped(map) => map,
+        }
+    }
+}
+
+impl From<Vec<u8>> for FileBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        FileBytes::Memory(bytes.into())
+    }
+}
+
+#[derive(Clone)]
 pub struct DatFile {
     pub name: String,
-    pub bytes: Vec<u8>,
+    pub bytes: FileBytes,
     pub total_size: usize,
     pub rows_begin: usize,
     pub data_section: usize,
@@ -31,11 +60,12 @@ impl std::fmt::Debug for DatFile {
 
 impl DatFile {
 
-    pub fn from_bytes(name: String, bytes: Vec<u8>) -> Result<DatFile, (String, String)> {
+    pub fn from_bytes(name: String, bytes: impl Into<FileBytes>) -> Result<DatFile, (String, String)> {
+        let bytes = bytes.into();
         if bytes.is_empty() {
             return Err((name, "No data provided to read the file from".to_string()));
         }
-        let mut cursor = Cursor::new(&bytes);
+        let mut cursor = Cursor::new(&bytes[..]);
         let Ok(rows_count) = cursor.read_u32::<LittleEndian>() else {
             return Err((name, "DAT file is empty".to_string()));
         };
@@ -155,6 +185,8 @@ trait ReadBytesToValue {
     fn read_value(&mut self, tag: &str) -> Value;
     fn bool(&mut self) -> Value;
     fn u8(&mut self) -> Value;
+    fn u16(&mut self) -> Value;
+    fn i16(&mut self) -> Value;
     fn u32(&mut self) -> Value;
     fn i32(&mut self) -> Value;
     fn f32(&mut self) -> Value;
@@ -169,6 +201,8 @@ impl ReadBytesToValue for Cursor<&[u8]> {
         match tag {
             "bool" => self.bool(),
             "u8"   => self.u8(),
+            "u16"  => self.u16(),
+            "i16"  => self.i16(),
             "u32"  => self.u32(),
             "i32"  => self.i32(),
             "f32"  => self.f32(),
@@ -202,6 +236,20 @@ impl ReadBytesToValue for Cursor<&[u8]> {
         }
     }
 
+    fn u16(&mut self) -> Value {
+        match self.read_u16::<LittleEndian>() {
+            Ok(value) => Value::U64(value as u64),
+            Err(_) => panic!("Unable to read u16"),
+        }
+    }
+
+    fn i16(&mut self) -> Value {
+        match self.read_i16::<LittleEndian>() {
+            Ok(value) => Value::I64(value as i64),
+            Err(_) => panic!("Unable to read i16"),
+        }
+    }
+
     fn u32(&mut self) -> Value {
         match self.read_u32::<LittleEndian>() {
             Ok(value) => u32_to_enum(value),
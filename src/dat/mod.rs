@@ -1,45 +1,172 @@
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
-use log::info;
+use log::{info, warn};
+use memmap2::Mmap;
 use poe_bundle::{BundleReader, BundleReaderRead};
 
-use crate::dat::file::DatFile;
+use crate::cache::{write_replacing, CachedBundles};
+use crate::dat::file::{DatFile, FileBytes};
 use crate::dat::specification::{EnumSpec, FileSpec};
 
 pub mod util;
 pub mod specification;
 pub mod file;
+pub mod object_template;
+pub mod passive_graph;
+pub mod stat_description;
 
 
 pub struct DatReader<'a> {
     language: &'a str,
-    bundle_reader: &'a BundleReader,
+    languages: Vec<String>,
+    files: GameFiles<'a>,
     specs: HashMap<String, FileSpec>,
     enums: HashMap<String, EnumSpec>,
+    /** Decoded tables by language and name, shared by every query and thread using the reader */
+    tables: RwLock<HashMap<String, DatFile>>,
+    /** Where decompressed tables are kept to be mapped instead of decompressed again */
+    file_cache: Option<PathBuf>,
+}
+
+/** Where the game files are read from */
+enum GameFiles<'a> {
+    Bundles(&'a BundleReader),
+    /** Bundles that are only indexed when a file isn't in the cache */
+    Cached(&'a CachedBundles),
+    /** Files extracted from the bundles, by their lowercase path */
+    Directory(HashMap<String, PathBuf>),
+}
+
+impl GameFiles<'_> {
+    fn bytes(&self, path: &str) -> Option<Vec<u8>> {
+        match self {
+            // TODO: remove unwrap() in poe_bundle and return an actual error
+            GameFiles::Bundles(bundles) => bundles.bytes(path).ok(),
+            // paths the index doesn't have are answered without indexing the bundles
+            GameFiles::Cached(cache) => cache.contains(path).then(|| cache.bundles().bytes(path).ok()).flatten(),
+            GameFiles::Directory(files) => std::fs::read(files.get(path)?).ok(),
+        }
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        match self {
+            GameFiles::Bundles(bundles) => bundles.size_of(path).is_some(),
+            GameFiles::Cached(cache) => cache.contains(path),
+            GameFiles::Directory(files) => files.contains_key(path),
+        }
+    }
 }
 
 impl<'a> DatReader<'a> {
 
     pub fn from_install(language: &'a str, bundles: &'a BundleReader, spec_path: &Path) -> DatReader<'a> {
+        Self::from_files(language, GameFiles::Bundles(bundles), &bundles.index.paths, spec_path)
+    }
+
+    /** Decompressed files are read from the cache, the bundles are indexed on the first file that isn't */
+    pub fn from_cache(language: &'a str, cache: &'a CachedBundles, spec_path: &Path) -> DatReader<'a> {
+        Self::from_files(language, GameFiles::Cached(cache), cache.paths(), spec_path).with_file_cache(&cache.files())
+    }
+
+    /** Reads files that were extracted from the bundles, like `<directory>/data/mods.datc64` */
+    pub fn from_directory(language: &'a str, directory: &Path, spec_path: &Path) -> DatReader<'a> {
+        let mut files = HashMap::new();
+        let mut pending = vec![directory.to_path_buf()];
+        while let Some(folder) = pending.pop() {
+            for path in std::fs::read_dir(&folder).into_iter().flatten().filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                if path.is_dir() {
+                    pending.push(path);
+                } else if let Ok(relative) = path.strip_prefix(directory) {
+                    let relative = relative.to_string_lossy().replace('\\', "/").to_lowercase();
+                    files.insert(relative, path);
+                }
+            }
+        }
+        let paths: Vec<String> = files.keys().cloned().collect();
+        Self::from_files(language, GameFiles::Directory(files), &paths, spec_path)
+    }
+
+    fn from_files(language: &'a str, files: GameFiles<'a>, paths: &[String], spec_path: &Path) -> DatReader<'a> {
         let enums = FileSpec::read_enum_specs(spec_path);
         let specs = FileSpec::read_file_specs(spec_path, &enums, &HashMap::new());
         let specs = FileSpec::read_file_specs(spec_path, &enums, &specs);
 
         DatReader {
             language,
-            bundle_reader: bundles,
+            languages: languages(paths),
+            files,
             specs,
-            enums
+            enums,
+            tables: RwLock::default(),
+            file_cache: None,
         }
     }
 
-    fn get_filepath(&self, filename: &str) -> String {
+    /** Keeps decompressed tables in `directory`, later readers map them from there */
+    pub fn with_file_cache(mut self, directory: &Path) -> DatReader<'a> {
+        self.file_cache = Some(directory.to_path_buf());
+        self
+    }
+
+    // tables without text only exist in English
+    fn get_filepath(&self, filename: &str, language: &str) -> String {
         let name = filename.to_lowercase();
-        if self.language == "English" {
-            return format!("data/{}.datc64", name)
+        let localized = format!("data/{}/{}.datc64", language.to_lowercase(), name);
+        match language.eq_ignore_ascii_case("English") || !self.files.contains(&localized) {
+            true => format!("data/{}.datc64", name),
+            false => localized,
         }
-        format!("data/{}/{}.datc64", self.language.to_lowercase(), name)
+    }
+
+    /** Languages in the bundle index, English first */
+    pub fn languages(&self) -> &[String] {
+        &self.languages
+    }
+
+    /** Like file_by_filename, but in another language than the one the reader was created for */
+    pub fn file_in_language(&self, filename: &str, language: &str) -> Option<DatFile> {
+        let key = format!("{}/{}", language.to_lowercase(), filename.to_lowercase());
+        if let Some(file) = self.tables.read().unwrap().get(&key) {
+            return Some(file.clone());
+        }
+        let path = self.get_filepath(filename, language);
+        let bytes = self.cached_bytes(&path)?;
+
+        let dat_file = DatFile::from_bytes(path, bytes).ok()?;
+        if let Some(file_specification) = self.spec(filename) {
+            dat_file.valid(file_specification);
+        }
+        // another thread may have decoded it meanwhile, keep the first so every copy shares its bytes
+        Some(self.tables.write().unwrap().entry(key).or_insert(dat_file).clone())
+    }
+
+    fn cached_bytes(&self, path: &str) -> Option<FileBytes> {
+        let Some(cached) = self.file_cache.as_ref().map(|directory| directory.join(path)) else {
+            info!("Unpacking {}", path);
+            return self.files.bytes(path).map(FileBytes::from);
+        };
+        if let Some(map) = map_file(&cached) {
+            info!("Mapping {}", cached.display());
+            return Some(FileBytes::Mapped(Arc::new(map)));
+        }
+        info!("Unpacking {}", path);
+        let bytes = self.files.bytes(path)?;
+        if let Err(error) = write_replacing(&cached, &bytes) {
+            warn!("Unable to cache {} in {}: {}", path, cached.display(), error);
+        }
+        Some(FileBytes::from(bytes))
+    }
+
+    pub fn language(&self) -> &str {
+        self.language
+    }
+
+    /** Any file in the bundles, paths are case insensitive */
+    pub fn file_bytes(&self, path: &str) -> Option<Vec<u8>> {
+        self.cached_bytes(&path.to_lowercase()).map(|bytes| bytes.to_vec())
     }
 }
 
@@ -53,20 +180,7 @@ pub trait DatStoreImpl<'a> {
 
 impl<'a> DatStoreImpl<'a> for DatReader<'a> {
     fn file_by_filename(&self, filename: &str) -> Option<DatFile> {
-        let path = self.get_filepath(filename);
-        let spec = self.spec(filename);
-        info!("Unpacking {}", path);
-        // TODO: remove unwrap() in poe_bundle and return an actual error
-        let Ok(bytes) = self.bundle_reader.bytes(&path) else { return None };
-
-        let dat_file = DatFile::from_bytes(path, bytes).ok();
-        match (spec, dat_file) {
-            (Some(file_specification), Some(dat_file)) => {
-                dat_file.valid(file_specification);
-                Some(dat_file)
-            },
-            (_, dat_file) => dat_file
-        }
+        self.file_in_language(filename, self.language)
     }
 
     fn spec(&self, path: &str) -> Option<&FileSpec> {
@@ -78,10 +192,39 @@ impl<'a> DatStoreImpl<'a> for DatReader<'a> {
     }
 
     fn exports(&self) -> HashSet<&str> {
-        self.specs.iter().map(|(_, s)| s.file_name.as_str()).collect()
+        self.specs.values().map(|s| s.file_name.as_str()).collect()
     }
 
     fn enum_name(&self, path: &str) -> Option<&EnumSpec> {
         self.enums.get(path)
     }
-}
\ No newline at end of file
+}
+
+fn map_file(path: &Path) -> Option<Mmap> {
+    let file = File::open(path).ok()?;
+    // cached files are only ever replaced by renaming a new file over them, never written in place
+    unsafe { Mmap::map(&file) }.ok().filter(|map| !map.is_empty())
+}
+
+/** Folders in `data/` that repeat tables from `data/` itself, named the way --language takes them */
+pub fn languages(paths: &[String]) -> Vec<String> {
+    let tables: HashSet<&str> = paths.iter()
+        .filter_map(|path| path.strip_prefix("data/"))
+        .filter(|name| !name.contains('/') && name.ends_with(".datc64"))
+        .collect();
+    let mut languages: Vec<String> = paths.iter()
+        .filter_map(|path| path.strip_prefix("data/")?.split_once('/'))
+        .filter(|(_, name)| tables.contains(name))
+        .map(|(folder, _)| folder.split(' ').map(capitalize).collect::<Vec<_>>().join(" "))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    languages.sort();
+    languages.insert(0, String::from("English"));
+    languages
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::dat::stat_description::decode;
+use crate::traversal::value::Value;
+
+const EXTENSIONS: [&str; 4] = [".ot", ".otc", ".it", ".itc"];
+
+/** Parsed `.it`, `.ot` and `.otc` files, each file is only read once */
+#[derive(Debug, Default)]
+pub struct ObjectTemplates {
+    templates: HashMap<String, Template>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Template {
+    extends: Vec<String>,
+    components: Vec<(String, Vec<(String, Value)>)>,
+}
+
+impl ObjectTemplates {
+    /** Components of the file with everything it extends merged in, the extension is optional */
+    pub fn resolve(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str) -> Result<Value, String> {
+        let path = self.find(read, path)?;
+        let template = self.merged(read, &path, &mut vec![])?;
+        Ok(Value::object(template.components.into_iter().map(|(name, entries)| (name, Value::object(entries)))))
+    }
+
+    /** Every value of a key in a component across the files it extends, parents first, like the tags of an item */
+    pub fn inherited(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, component: &str, key: &str) -> Result<Vec<Value>, String> {
+        let path = self.find(read, path)?;
+        let mut values = Vec::new();
+        self.collect(read, &path, component, key, &mut values, &mut vec![])?;
+        Ok(values)
+    }
+
+    fn find(&self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str) -> Result<String, String> {
+        let path = path.to_lowercase();
+        match EXTENSIONS.iter().any(|extension| path.ends_with(extension)) {
+            true => Ok(path),
+            false => EXTENSIONS.iter()
+                .map(|extension| format!("{}{}", path, extension))
+                .find(|candidate| self.templates.contains_key(candidate) || read(candidate).is_some())
+                .ok_or_else(|| format!("No object file for {}", path)),
+        }
+    }
+
+    fn template(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, chain: &[String]) -> Result<Template, String> {
+        if chain.iter().any(|extending| extending == path) {
+            return Err(format!("{} extends itself", path));
+        }
+        if !self.templates.contains_key(path) {
+            let bytes = read(path).ok_or_else(|| format!("Failed to read {}", path))?;
+            self.templates.insert(path.to_string(), parse(&decode(&bytes))?);
+        }
+        Ok(self.templates[path].clone())
+    }
+
+    // parents use the extension of the file extending them, "nothing" extends no file
+    fn parents(template: &Template, path: &str) -> Vec<String> {
+        let extension = path.rfind('.').map_or("", |dot| &path[dot..]);
+        template.extends.iter()
+            .filter(|parent| parent.as_str() != "nothing")
+            .map(|parent| format!("{}{}", parent.to_lowercase(), extension))
+            .collect()
+    }
+
+    fn merged(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, chain: &mut Vec<String>) -> Result<Template, String> {
+        let template = self.template(read, path, chain)?;
+
+        // parents are extended in order, the file itself has the last word
+        chain.push(path.to_string());
+        let mut merged = Template::default();
+        for parent in Self::parents(&template, path) {
+            let parent = self.merged(read, &parent, chain)?;
+            merge(&mut merged.components, parent.components);
+        }
+        chain.pop();
+        merge(&mut merged.components, template.components);
+        Ok(merged)
+    }
+
+    fn collect(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, component: &str, key: &str, values: &mut Vec<Value>, chain: &mut Vec<String>) -> Result<(), String> {
+        let template = self.template(read, path, chain)?;
+
+        chain.push(path.to_string());
+        for parent in Self::parents(&template, path) {
+            self.collect(read, &parent, component, key, values, chain)?;
+        }
+        chain.pop();
+        let entries = template.components.iter().filter(|(name, _)| name == component).flat_map(|(_, entries)| entries);
+        for (_, value) in entries.filter(|(name, _)| name == key) {
+            match value {
+                Value::List(list) => values.extend(list.iter().cloned()),
+                value => values.push(value.clone()),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn merge(components: &mut Vec<(String, Vec<(String, Value)>)>, overrides: Vec<(String, Vec<(String, Value)>)>) {
+    for (name, entries) in overrides {
+        match components.iter_mut().find(|(component, _)| *component == name) {
+            Some((_, existing)) => {
+                for (key, value) in entries {
+                    match existing.iter_mut().find(|(existing, _)| *existing == key) {
+                        Some(entry) => entry.1 = value,
+                        None => existing.push((key, value)),
+                    }
+                }
+            }
+            None => components.push((name, entries)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+    Equals,
+}
+
+fn parse(text: &str) -> Result<Template, String> {
+    let mut tokens = tokens(text).into_iter().peekable();
+    let mut template = Template::default();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) if word == "version" => {
+                tokens.next();
+            }
+            Token::Word(word) if word == "abstract" => {}
+            Token::Word(word) if word == "extends" => match tokens.next() {
+                Some(Token::Quoted(parent) | Token::Word(parent)) => template.extends.push(parent),
+                unexpected => return Err(format!("Expected a file after extends, got {:?}", unexpected)),
+            },
+            Token::Word(name) if tokens.next_if_eq(&Token::Open).is_some() => {
+                let entries = block(&mut tokens)?;
+                merge(&mut template.components, vec![(name, entries)]);
+            }
+            unexpected => return Err(format!("Unexpected {:?} outside of a component", unexpected)),
+        }
+    }
+    Ok(template)
+}
+
+// `key = value` pairs up to the closing brace, blocks can nest and a key can repeat
+fn block(tokens: &mut std::iter::Peekable<impl Iterator<Item = Token>>) -> Result<Vec<(String, Value)>, String> {
+    let mut entries: Vec<(String, Value)> = Vec::new();
+    loop {
+        let (key, value) = match tokens.next() {
+            Some(Token::Close) => return Ok(entries),
+            Some(Token::Word(key) | Token::Quoted(key)) => match tokens.next_if(|token| matches!(token, Token::Equals | Token::Open)) {
+                Some(Token::Equals) => match tokens.next() {
+                    Some(Token::Quoted(text)) => (key, Value::Str(text)),
+                    Some(Token::Word(word)) => (key, scalar(&word)),
+                    unexpected => return Err(format!("Expected a value for {}, got {:?}", key, unexpected)),
+                },
+                Some(_) => (key, Value::object(block(tokens)?)),
+                // a bare word is a flag
+                None => (key, Value::Bool(true)),
+            },
+            unexpected => return Err(format!("Unexpected {:?} in a component", unexpected)),
+        };
+        match entries.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, Value::List(values))) => values.push(value),
+            Some((_, existing)) => *existing = Value::List(vec![existing.clone(), value]),
+            None => entries.push((key, value)),
+        }
+    }
+}
+
+fn tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '=' => tokens.push(Token::Equals),
+            '"' => tokens.push(Token::Quoted(chars.by_ref().take_while(|c| *c != '"').collect())),
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '{' | '}' | '=' | '"')) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    tokens
+}
+
+fn scalar(word: &str) -> Value {
+    match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ if !word.starts_with(|c: char| c.is_ascii_digit() || c == '-') => Value::Str(word.to_string()),
+        _ => word.parse::<i64>().map(Value::I64)
+            .or_else(|_| word.parse::<f32>().map(Value::F32))
+            .unwrap_or_else(|_| Value::Str(word.to_string())),
+    }
+}
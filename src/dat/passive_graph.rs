@@ -0,0 +1,86 @@
+use std::io::{Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+/** The graph of the character passive tree, the atlas tree has one of its own */
+pub const PASSIVE_SKILL_GRAPH: &str = "Metadata/PassiveSkillGraph.psg";
+
+/** A parsed `.psg` file, passives are identified by `PassiveSkills.PassiveSkillGraphId` */
+#[derive(Debug, Default, PartialEq)]
+pub struct PassiveGraph {
+    pub roots: Vec<u32>,
+    pub groups: Vec<Group>,
+}
+
+/** Passives placed around a shared centre */
+#[derive(Debug, Default, PartialEq)]
+pub struct Group {
+    pub x: f32,
+    pub y: f32,
+    pub flags: u32,
+    pub passives: Vec<Passive>,
+}
+
+/** A passive at `orbit_index` on one of the orbits of its group */
+#[derive(Debug, Default, PartialEq)]
+pub struct Passive {
+    pub id: u32,
+    pub orbit: u32,
+    pub orbit_index: u32,
+    pub connections: Vec<Connection>,
+}
+
+/** An edge to another passive, the curvature bends it along an orbit */
+#[derive(Debug, Default, PartialEq)]
+pub struct Connection {
+    pub id: u32,
+    pub curvature: i32,
+}
+
+impl PassiveGraph {
+    /** Reads version 3 of the format, the only one current patches use */
+    pub fn parse(bytes: &[u8]) -> Result<PassiveGraph, String> {
+        // u8 version, u8 graph type, u32 root count and a u64 per root, u32 group count,
+        // per group f32 x, f32 y, u32 flags, five unknown bytes and a u32 passive count,
+        // per passive u32 id, orbit, orbit index and connection count, a u32 id and i32 curvature per connection
+        let mut cursor = Cursor::new(bytes);
+        let truncated = |_| String::from("The passive skill graph ends early");
+        let version = cursor.read_u8().map_err(truncated)?;
+        if version != 3 {
+            return Err(format!("Unsupported passive skill graph version {}", version));
+        }
+        cursor.read_u8().map_err(truncated)?;
+
+        let mut graph = PassiveGraph::default();
+        for _ in 0..cursor.read_u32::<LittleEndian>().map_err(truncated)? {
+            // the upper half is always zero
+            graph.roots.push(cursor.read_u64::<LittleEndian>().map_err(truncated)? as u32);
+        }
+        for _ in 0..cursor.read_u32::<LittleEndian>().map_err(truncated)? {
+            let mut group = Group {
+                x: cursor.read_f32::<LittleEndian>().map_err(truncated)?,
+                y: cursor.read_f32::<LittleEndian>().map_err(truncated)?,
+                flags: cursor.read_u32::<LittleEndian>().map_err(truncated)?,
+                passives: vec![],
+            };
+            cursor.read_exact(&mut [0; 5]).map_err(truncated)?;
+            for _ in 0..cursor.read_u32::<LittleEndian>().map_err(truncated)? {
+                let mut passive = Passive {
+                    id: cursor.read_u32::<LittleEndian>().map_err(truncated)?,
+                    orbit: cursor.read_u32::<LittleEndian>().map_err(truncated)?,
+                    orbit_index: cursor.read_u32::<LittleEndian>().map_err(truncated)?,
+                    connections: vec![],
+                };
+                for _ in 0..cursor.read_u32::<LittleEndian>().map_err(truncated)? {
+                    passive.connections.push(Connection {
+                        id: cursor.read_u32::<LittleEndian>().map_err(truncated)?,
+                        curvature: cursor.read_i32::<LittleEndian>().map_err(truncated)?,
+                    });
+                }
+                group.passives.push(passive);
+            }
+            graph.groups.push(group);
+        }
+        Ok(graph)
+    }
+}
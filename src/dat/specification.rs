@@ -38,6 +38,12 @@ pub struct FieldSpec {
     pub enum_name: Option<EnumSpec>,
     pub field_size: usize,
     pub field_offset: usize,
+    /** Identifies a row across game versions, marked with @unique */
+    #[serde(default)]
+    pub unique: bool,
+    /** Differs between the languages of the game, marked with @localized */
+    #[serde(default)]
+    pub localized: bool,
 }
 
 impl fmt::Display for FileSpec {
@@ -97,7 +103,7 @@ impl FileSpec {
     {
         std::fs::read_dir(path).expect("spec path does not exist")
             .filter_map(|directory| directory.ok().map(|entry| entry.path()))
-            .filter(|file_path| file_path.is_file() && file_path.extension().map_or(false, |ext| ext == "gql"))
+            .filter(|file_path| file_path.is_file() && file_path.extension().is_some_and(|ext| ext == "gql"))
             .flat_map(|file_path| {
                 let text = std::fs::read_to_string(file_path).unwrap();
 
@@ -125,6 +131,8 @@ impl FileSpec {
                         let name = field.name().unwrap().text();
 
                         let mut is_path_field = false;
+                        let mut unique = false;
+                        let mut localized = false;
                         let mut reference_key = None;
                         if let Some(field_directives) = field.directives().map(|x| x.directives()) {
                             for directive in field_directives {
@@ -132,6 +140,12 @@ impl FileSpec {
                                 if directive.name().unwrap().text().as_str() == "file" {
                                     is_path_field = true;
                                 }
+                                if directive.name().unwrap().text().as_str() == "unique" {
+                                    unique = true;
+                                }
+                                if directive.name().unwrap().text().as_str() == "localized" {
+                                    localized = true;
+                                }
                                 // @ref(column: "Id")
                                 if directive.name().unwrap().text().as_str() == "ref" {
                                     let first = directive.arguments().unwrap().arguments().find(|x| x.name().unwrap().text() == "column").unwrap().value().unwrap().syntax().text().to_string();
@@ -163,13 +177,13 @@ impl FileSpec {
                         };
 
                         let key_file = match type_name.as_str() {
-                            "i32" | "bool" | "string" | "f32" | "u32" | "path" | "_" => None,
+                            "i32" | "bool" | "string" | "f32" | "u32" | "u16" | "i16" | "path" | "_" => None,
                             _ if enum_spec.is_some() => None,
                             fk => Some(fk.to_string())
                         };
 
-                        if reference_key.is_some() && key_file.is_some() {
-                            match file_specs.get(key_file.as_ref().unwrap()) {
+                        if let (Some(_), Some(key_file)) = (&reference_key, &key_file) {
+                            match file_specs.get(key_file) {
                                 None => {}
                                 Some(file_spec) => {
                                     type_name = file_spec.file_fields.iter()
@@ -182,6 +196,7 @@ impl FileSpec {
 
                         let mut field_size: usize = match type_name.as_str() {
                             "bool" | "u8" => 1,
+                            "u16" | "i16" => 2,
                             "u32" | "i32" | "f32" => 4,
                             "i64" | "u64" | "string" | "path" => 8,
                             _ if reference_key.is_some() && key_file.is_some() => {
@@ -230,6 +245,8 @@ impl FileSpec {
                             enum_name: enum_spec.cloned(),
                             field_size,
                             field_offset: current_offset,
+                            unique,
+                            localized,
                         });
                     }
 
@@ -254,6 +271,7 @@ impl FileSpec {
         match datatype {
             "u64" | "i64" | "list" => 8,
             "bool" | "u8" => 1,
+            "u16" | "i16" => 2,
             _ => 4,
         }
     }
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+/** The descriptions mods and most items use */
+pub const STAT_DESCRIPTIONS: &str = "Metadata/StatDescriptions/stat_descriptions.txt";
+
+/** Parsed files in `Metadata/StatDescriptions`, the text the game shows for stat values */
+#[derive(Debug, Default)]
+pub struct StatDescriptions {
+    descriptions: Vec<Description>,
+    by_stat: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+struct Description {
+    stats: Vec<String>,
+    variants: Vec<Variant>,
+}
+
+#[derive(Debug)]
+struct Variant {
+    conditions: Vec<Condition>,
+    text: String,
+    handlers: Vec<(String, usize)>,
+}
+
+#[derive(Debug)]
+enum Condition {
+    Range(Option<i64>, Option<i64>),
+    Not(i64),
+}
+
+impl StatDescriptions {
+    /** Reads `path` and the files it includes, `read` gets the raw bytes of a file in the bundles */
+    pub fn load(read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, language: &str) -> Result<StatDescriptions, String> {
+        let mut descriptions = StatDescriptions::default();
+        descriptions.load_into(read, path, language, &mut vec![])?;
+        Ok(descriptions)
+    }
+
+    fn load_into(&mut self, read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str, language: &str, loading: &mut Vec<String>) -> Result<(), String> {
+        if loading.iter().any(|loaded| loaded.eq_ignore_ascii_case(path)) {
+            return Err(format!("{} includes itself", path));
+        }
+        let bytes = read(path).ok_or_else(|| format!("Failed to read {}", path))?;
+        loading.push(path.to_string());
+        let (includes, descriptions) = parse(&decode(&bytes), language);
+        for include in includes {
+            self.load_into(read, &include, language, loading)?;
+        }
+        descriptions.into_iter().for_each(|description| self.push(description));
+        loading.pop();
+        Ok(())
+    }
+
+    /** Descriptions in a single file, includes are ignored */
+    pub fn parse(text: &str, language: &str) -> StatDescriptions {
+        let mut descriptions = StatDescriptions::default();
+        parse(text, language).1.into_iter().for_each(|description| descriptions.push(description));
+        descriptions
+    }
+
+    // later descriptions of a stat replace earlier ones, which is how files override what they include
+    fn push(&mut self, description: Description) {
+        for stat in &description.stats {
+            self.by_stat.insert(stat.clone(), self.descriptions.len());
+        }
+        self.descriptions.push(description);
+    }
+
+    /** Lines of text for stats with a value range each, in the order the game shows them */
+    pub fn describe(&self, stats: &[(String, i64, i64)]) -> Vec<String> {
+        let mut used: Vec<usize> = stats.iter().filter_map(|(id, _, _)| self.by_stat.get(id).copied()).collect();
+        used.sort();
+        used.dedup();
+
+        used.into_iter()
+            .filter_map(|index| {
+                let description = &self.descriptions[index];
+                let values: Vec<(i64, i64)> = description.stats.iter()
+                    .map(|id| stats.iter().find(|(stat, _, _)| stat == id).map_or((0, 0), |(_, min, max)| (*min, *max)))
+                    .collect();
+                if values.iter().all(|value| *value == (0, 0)) {
+                    return None;
+                }
+                // a range can straddle conditions, the maximum decides then
+                let variant = description.variants.iter()
+                    .find(|variant| variant.matches(&values, |(min, max)| vec![min, max]))
+                    .or_else(|| description.variants.iter().find(|variant| variant.matches(&values, |(_, max)| vec![max])))?;
+                Some(variant.render(&values))
+            })
+            .collect()
+    }
+}
+
+impl Variant {
+    fn matches(&self, values: &[(i64, i64)], checked: fn((i64, i64)) -> Vec<i64>) -> bool {
+        self.conditions.iter().zip(values).all(|(condition, value)| {
+            checked(*value).into_iter().all(|value| match condition {
+                Condition::Range(min, max) => min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max),
+                Condition::Not(excluded) => value != *excluded,
+            })
+        })
+    }
+
+    fn render(&self, values: &[(i64, i64)]) -> String {
+        let mut values: Vec<(f64, f64)> = values.iter().map(|(min, max)| (*min as f64, *max as f64)).collect();
+        for (handler, index) in &self.handlers {
+            if let Some((min, max)) = index.checked_sub(1).and_then(|index| values.get_mut(index)) {
+                (*min, *max) = (handle(handler, *min), handle(handler, *max));
+            }
+        }
+        // negating handlers turn a range around, e.g. (-20--10) is shown as (10-20)
+        for (min, max) in values.iter_mut().filter(|(min, max)| min > max) {
+            std::mem::swap(min, max);
+        }
+
+        let mut text = String::new();
+        let mut next = 0;
+        let mut rest = self.text.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else { break };
+            text.push_str(&rest[..start]);
+            let (index, format) = rest[start + 1..end].split_once(':').unwrap_or((&rest[start + 1..end], ""));
+            let index = index.parse::<usize>().unwrap_or(next);
+            next = index + 1;
+            match values.get(index) {
+                Some(value) => text.push_str(&range(*value, format.contains('+'))),
+                None => text.push_str(&rest[start..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        markup(&text).replace("\\n", "\n")
+    }
+}
+
+/** The files are UTF-16 with a byte order mark */
+pub fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => {
+            let units = rest.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+            char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+        }
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).to_string(),
+        bytes => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+fn parse(text: &str, language: &str) -> (Vec<String>, Vec<Description>) {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty()).peekable();
+    let mut includes = Vec::new();
+    let mut descriptions = Vec::new();
+    while let Some(line) = lines.next() {
+        let words = tokens(line);
+        match words.first().map(|(token, _)| token.as_str()) {
+            Some("include") => includes.extend(words.get(1).map(|(path, _)| path.clone())),
+            Some("description") => {
+                let Some(header) = lines.next().map(tokens) else { break };
+                let count = header.first().and_then(|(count, _)| count.parse::<usize>().ok()).unwrap_or(0);
+                let stats: Vec<String> = header.into_iter().skip(1).take(count).map(|(stat, _)| stat).collect();
+
+                let mut variants = read_variants(&mut lines, stats.len());
+                while let Some(line) = lines.next_if(|line| line.starts_with("lang ")) {
+                    let translated = read_variants(&mut lines, stats.len());
+                    let name = tokens(line).into_iter().nth(1).map(|(name, _)| name).unwrap_or_default();
+                    if name.eq_ignore_ascii_case(language) {
+                        variants = translated;
+                    }
+                }
+                descriptions.push(Description { stats, variants });
+            }
+            _ => {}
+        }
+    }
+    (includes, descriptions)
+}
+
+// a count followed by that many lines of `conditions "text" handlers`
+fn read_variants<'a>(lines: &mut impl Iterator<Item = &'a str>, stats: usize) -> Vec<Variant> {
+    let count = lines.next().and_then(|line| line.parse::<usize>().ok()).unwrap_or(0);
+    lines.take(count)
+        .map(|line| {
+            let tokens = tokens(line);
+            let conditions = tokens.iter().take(stats).map(|(token, _)| condition(token)).collect();
+            let text = tokens.iter().find(|(_, quoted)| *quoted).map(|(text, _)| text.clone()).unwrap_or_default();
+            let mut handlers: Vec<(String, usize)> = Vec::new();
+            for (token, _) in tokens.into_iter().skip_while(|(_, quoted)| !quoted).skip(1) {
+                match (token.parse::<usize>(), handlers.last_mut()) {
+                    (Ok(index), Some(handler)) => handler.1 = index,
+                    _ => handlers.push((token, 1)),
+                }
+            }
+            Variant { conditions, text, handlers }
+        })
+        .collect()
+}
+
+// words and "quoted strings", the flag tells them apart
+fn tokens(line: &str) -> Vec<(String, bool)> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            tokens.push((quoted[..end].to_string(), true));
+            rest = quoted.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push((rest[..end].to_string(), false));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    tokens
+}
+
+// `#` is any value, `1|#` at least one, `#|-1` at most minus one, `!0` anything but zero
+fn condition(token: &str) -> Condition {
+    let bound = |text: &str| text.parse::<i64>().ok();
+    match (token.split_once('|'), token.strip_prefix('!')) {
+        (Some((min, max)), _) => Condition::Range(bound(min), bound(max)),
+        (None, Some(excluded)) => bound(excluded).map_or(Condition::Range(None, None), Condition::Not),
+        (None, None) => Condition::Range(bound(token), bound(token)),
+    }
+}
+
+fn handle(handler: &str, value: f64) -> f64 {
+    let handler = handler.trim_end_matches("_if_required");
+    let (handler, decimals) = match handler.rsplit_once('_') {
+        Some((name, "0dp")) => (name, Some(0)),
+        Some((name, "1dp")) => (name, Some(1)),
+        Some((name, "2dp")) => (name, Some(2)),
+        _ => (handler, None),
+    };
+    let value = match handler {
+        "negate" => -value,
+        "negate_and_double" => -2.0 * value,
+        "double" => 2.0 * value,
+        "times_twenty" => 20.0 * value,
+        "times_one_point_five" => 1.5 * value,
+        "30%_of_value" => 0.3 * value,
+        "60%_of_value" => 0.6 * value,
+        "per_minute_to_per_second" => value / 60.0,
+        "milliseconds_to_seconds" => value / 1000.0,
+        "deciseconds_to_seconds" => value / 10.0,
+        "multiplicative_damage_modifier" => value + 100.0,
+        "multiplicative_permyriad_damage_modifier" => value / 100.0 + 100.0,
+        handler => match handler.strip_prefix("divide_by_").and_then(divisor) {
+            Some(divisor) => value / divisor,
+            None => value,
+        },
+    };
+    match decimals {
+        Some(decimals) => (value * 10f64.powi(decimals)).round() / 10f64.powi(decimals),
+        None => value,
+    }
+}
+
+fn divisor(name: &str) -> Option<f64> {
+    let divisor = match name {
+        "two" => 2.0,
+        "three" => 3.0,
+        "four" => 4.0,
+        "five" => 5.0,
+        "six" => 6.0,
+        "ten" => 10.0,
+        "twelve" => 12.0,
+        "fifteen" => 15.0,
+        "twenty" => 20.0,
+        "fifty" => 50.0,
+        "one_hundred" => 100.0,
+        "one_thousand" => 1000.0,
+        _ => return None,
+    };
+    Some(divisor)
+}
+
+// (10-20) for a range, + in front of positive values when the format asks for it
+fn range((min, max): (f64, f64), plus: bool) -> String {
+    let sign = if plus && min >= 0.0 && max > 0.0 { "+" } else { "" };
+    match min == max {
+        true => format!("{}{}", sign, number(min)),
+        false => format!("{}({}-{})", sign, number(min), number(max)),
+    }
+}
+
+fn number(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// [Id|Shown] links to the in game glossary, [Shown] when both are the same
+fn markup(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        let Some(end) = rest[start..].find(']').map(|end| start + end) else { break };
+        result.push_str(&rest[..start]);
+        let link = &rest[start + 1..end];
+        result.push_str(link.rsplit_once('|').map_or(link, |(_, shown)| shown));
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,149 @@
+use std::io::Read;
+
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+const FOURCC: u32 = 0x4;
+
+/** A decoded texture with four bytes per pixel */
+#[derive(Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc7,
+    // bits per pixel and the red, green, blue and alpha masks
+    Uncompressed(u32, [u32; 4]),
+}
+
+/** Decodes the texture at `path`, following files that only refer to another texture */
+pub fn read(read: &dyn Fn(&str) -> Option<Vec<u8>>, path: &str) -> Result<Image, String> {
+    let mut path = path.to_string();
+    let mut followed = vec![];
+    loop {
+        let bytes = read(&path).ok_or_else(|| format!("Failed to read {}", path))?;
+        let bytes = unwrap(bytes)?;
+        let Some(target) = bytes.strip_prefix(b"*") else { return decode(&bytes) };
+        followed.push(path);
+        path = String::from_utf8_lossy(target).trim().to_lowercase();
+        if followed.contains(&path) {
+            return Err(format!("{} refers to itself", path));
+        }
+    }
+}
+
+/** Decodes the top mip level of a DDS file, it can be brotli compressed like in the bundles */
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let bytes = unwrap(bytes.to_vec())?;
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(b"DDS ") {
+        return Err(String::from("Not a DDS file"));
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+    let (height, width) = (u32_at(12) as usize, u32_at(16) as usize);
+
+    let (format, data) = match &bytes[84..88] {
+        b"DX10" if bytes.len() >= HEADER_SIZE + DX10_HEADER_SIZE => (dxgi_format(u32_at(128))?, &bytes[HEADER_SIZE + DX10_HEADER_SIZE..]),
+        fourcc if u32_at(80) & FOURCC != 0 => (fourcc_format(fourcc)?, &bytes[HEADER_SIZE..]),
+        _ => (Format::Uncompressed(u32_at(88), [u32_at(92), u32_at(96), u32_at(100), u32_at(104)]), &bytes[HEADER_SIZE..]),
+    };
+
+    let mut pixels = vec![0u32; width * height];
+    let decoded = match format {
+        Format::Bc1 => texture2ddecoder::decode_bc1a(data, width, height, &mut pixels),
+        Format::Bc2 => texture2ddecoder::decode_bc2(data, width, height, &mut pixels),
+        Format::Bc3 => texture2ddecoder::decode_bc3(data, width, height, &mut pixels),
+        Format::Bc4 => texture2ddecoder::decode_bc4(data, width, height, &mut pixels),
+        Format::Bc5 => texture2ddecoder::decode_bc5(data, width, height, &mut pixels),
+        Format::Bc7 => texture2ddecoder::decode_bc7(data, width, height, &mut pixels),
+        Format::Uncompressed(bits, masks) => return uncompressed(data, width, height, bits, masks),
+    };
+    decoded.map_err(String::from)?;
+
+    // the decoder writes little endian BGRA
+    let rgba = pixels.into_iter()
+        .flat_map(|pixel| {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            [r, g, b, a]
+        })
+        .collect();
+    Ok(Image { width, height, rgba })
+}
+
+/** Encodes the image as PNG */
+pub fn png(image: &Image) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.rgba))
+        .map_err(|error| error.to_string())?;
+    Ok(bytes)
+}
+
+// textures in the bundles are the uncompressed size followed by a brotli stream
+fn unwrap(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    if bytes.starts_with(b"DDS ") || bytes.starts_with(b"*") || bytes.len() < 4 {
+        return Ok(bytes);
+    }
+    let size = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let mut decompressed = Vec::with_capacity(size);
+    brotli_decompressor::Decompressor::new(&bytes[4..], 4096)
+        .read_to_end(&mut decompressed)
+        .map_err(|error| format!("Failed to decompress texture: {}", error))?;
+    Ok(decompressed)
+}
+
+fn fourcc_format(fourcc: &[u8]) -> Result<Format, String> {
+    match fourcc {
+        b"DXT1" => Ok(Format::Bc1),
+        b"DXT2" | b"DXT3" => Ok(Format::Bc2),
+        b"DXT4" | b"DXT5" => Ok(Format::Bc3),
+        b"ATI1" | b"BC4U" => Ok(Format::Bc4),
+        b"ATI2" | b"BC5U" => Ok(Format::Bc5),
+        fourcc => Err(format!("Unsupported DDS format {}", String::from_utf8_lossy(fourcc))),
+    }
+}
+
+fn dxgi_format(format: u32) -> Result<Format, String> {
+    match format {
+        28 | 29 => Ok(Format::Uncompressed(32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000])),
+        87 | 91 => Ok(Format::Uncompressed(32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000])),
+        88 | 93 => Ok(Format::Uncompressed(32, [0xFF0000, 0xFF00, 0xFF, 0])),
+        71 | 72 => Ok(Format::Bc1),
+        74 | 75 => Ok(Format::Bc2),
+        77 | 78 => Ok(Format::Bc3),
+        80 => Ok(Format::Bc4),
+        83 => Ok(Format::Bc5),
+        98 | 99 => Ok(Format::Bc7),
+        format => Err(format!("Unsupported DXGI format {}", format)),
+    }
+}
+
+// each channel is wherever its mask says, without an alpha mask the pixels are opaque
+fn uncompressed(data: &[u8], width: usize, height: usize, bits: u32, masks: [u32; 4]) -> Result<Image, String> {
+    let size = bits as usize / 8;
+    if !(1..=4).contains(&size) || data.len() < width * height * size {
+        return Err(format!("Unsupported or truncated {} bit DDS", bits));
+    }
+    let channel = |pixel: u32, mask: u32| match mask {
+        0 => 255,
+        mask => (((pixel & mask) >> mask.trailing_zeros()) as u64 * 255 / (mask >> mask.trailing_zeros()) as u64) as u8,
+    };
+    let rgba = data.chunks_exact(size)
+        .take(width * height)
+        .flat_map(|chunk| {
+            let pixel = chunk.iter().rev().fold(0u32, |pixel, byte| pixel << 8 | *byte as u32);
+            masks.map(|mask| channel(pixel, mask))
+        })
+        .collect();
+    Ok(Image { width, height, rgba })
+}
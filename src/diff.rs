@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use log::*;
+
+use crate::dat::DatStoreImpl;
+use crate::dat::file::DatFile;
+use crate::dat::specification::FileSpec;
+use crate::traversal::value::Value;
+
+// the key of a row and its fields
+type Row = (Value, Vec<(String, Value)>);
+
+/** Rows that were added, removed or changed in each table, one record per row */
+pub fn diff<'a, 'b>(old: &impl DatStoreImpl<'a>, new: &impl DatStoreImpl<'b>, tables: &[&str]) -> Value {
+    let mut old = Version::new(old);
+    let mut new = Version::new(new);
+    let mut records = Vec::new();
+    for table in tables {
+        let (before, after) = (old.file(table), new.file(table));
+        match (&before, &after) {
+            (None, None) => warn!("{} is in neither version", table),
+            // identical files are never decoded
+            (Some(before), Some(after)) if *before.bytes == *after.bytes => debug!("{} is unchanged", table),
+            _ => records.extend(compare(table, old.rows(table, before.as_ref()), new.rows(table, after.as_ref()))),
+        }
+    }
+    Value::Iterator(records)
+}
+
+fn compare(table: &str, before: Vec<Row>, after: Vec<Row>) -> Vec<Value> {
+    let record = |change: &str, key: &Value, entries: (&str, Value)| Value::object(vec![
+        ("table", Value::Str(table.to_string())),
+        ("change", Value::Str(change.to_string())),
+        ("key", key.clone()),
+        entries,
+    ]);
+    let row = |fields: &[(String, Value)]| Value::object(fields.iter().map(|(name, value)| (name.as_str(), value.clone())));
+
+    let mut removed: HashMap<String, &Row> = before.iter().map(|row| (format!("{:?}", row.0), row)).collect();
+    let mut records = Vec::new();
+    for (key, fields) in &after {
+        let Some((_, previous)) = removed.remove(&format!("{:?}", key)) else {
+            records.push(record("added", key, ("row", row(fields))));
+            continue;
+        };
+        // fields only one of the schemas has are null in the other
+        let mut names: Vec<&String> = Vec::new();
+        for (name, _) in previous.iter().chain(fields) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let value = |fields: &[(String, Value)], name: &String| fields.iter().find(|(field, _)| field == name).map_or(Value::Empty, |(_, value)| value.clone());
+        let changed: Vec<(&str, Value)> = names.into_iter()
+            .map(|name| (name, value(previous, name), value(fields, name)))
+            .filter(|(_, old, new)| old != new)
+            .map(|(name, old, new)| (name.as_str(), Value::object(vec![("old", old), ("new", new)])))
+            .collect();
+        if !changed.is_empty() {
+            records.push(record("changed", key, ("fields", Value::object(changed))));
+        }
+    }
+    // removed rows keep their order from the old version
+    for (key, fields) in &before {
+        if removed.contains_key(&format!("{:?}", key)) {
+            records.push(record("removed", key, ("row", row(fields))));
+        }
+    }
+    records
+}
+
+/** One side of the diff, the unique keys of tables referenced by foreign keys are kept around */
+struct Version<'s, S> {
+    store: &'s S,
+    keys: HashMap<String, Option<Vec<Value>>>,
+}
+
+impl<'s, 'a, S: DatStoreImpl<'a>> Version<'s, S> {
+    fn new(store: &'s S) -> Self {
+        Version { store, keys: HashMap::new() }
+    }
+
+    fn file(&self, table: &str) -> Option<DatFile> {
+        self.store.spec(table)?;
+        self.store.file_by_filename(table)
+    }
+
+    // rows with their key, foreign keys point at the key of the referenced row instead of its index
+    fn rows(&mut self, table: &str, file: Option<&DatFile>) -> Vec<Row> {
+        let (Some(file), Some(spec)) = (file, self.store.spec(table).cloned()) else { return vec![] };
+        let keys = self.keys(table, &spec, file);
+        (0..file.rows_count as u64)
+            .map(|i| {
+                let fields = spec.file_fields.iter()
+                    .map(|field| {
+                        let value = file.read_field(i, field);
+                        let value = match &field.file_name {
+                            Some(target) => self.resolve(target, value),
+                            None => value,
+                        };
+                        (field.field_name.clone(), value)
+                    })
+                    .collect();
+                let key = keys.as_ref().and_then(|keys| keys.get(i as usize).cloned()).unwrap_or(Value::U64(i));
+                (key, fields)
+            })
+            .collect()
+    }
+
+    fn keys(&mut self, table: &str, spec: &FileSpec, file: &DatFile) -> Option<Vec<Value>> {
+        let keys = self.keys.entry(table.to_string()).or_insert_with(|| {
+            let unique = spec.file_fields.iter().find(|field| field.unique)?;
+            Some((0..file.rows_count as u64).map(|i| file.read_field(i, unique)).collect())
+        });
+        keys.clone()
+    }
+
+    fn resolve(&mut self, table: &str, value: Value) -> Value {
+        if !self.keys.contains_key(table) {
+            let keys = match self.store.spec(table).cloned().zip(self.store.file_by_filename(table)) {
+                Some((spec, file)) => self.keys(table, &spec, &file),
+                None => None,
+            };
+            self.keys.insert(table.to_string(), keys);
+        }
+        let Some(keys) = &self.keys[table] else { return value };
+        match value {
+            Value::U64(index) => keys.get(index as usize).cloned().unwrap_or(Value::U64(index)),
+            Value::List(values) => Value::List(values.into_iter()
+                .map(|value| match value {
+                    Value::U64(index) => keys.get(index as usize).cloned().unwrap_or(Value::U64(index)),
+                    value => value,
+                })
+                .collect()),
+            value => value,
+        }
+    }
+}
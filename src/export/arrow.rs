@@ -0,0 +1,217 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array, Int64Array, ListArray, NullArray, RecordBatch, StringArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use log::*;
+use parquet::arrow::ArrowWriter;
+
+use crate::dat::DatStoreImpl;
+use crate::dat::file::DatFile;
+use crate::dat::specification::{FieldSpec, FileSpec};
+use crate::export::{column_names, INDEX_COLUMN};
+use crate::output::table;
+use crate::traversal::format::json;
+use crate::traversal::value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columnar {
+    Parquet,
+    ArrowIpc,
+}
+
+impl Columnar {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Columnar::Parquet => "parquet",
+            Columnar::ArrowIpc => "arrow",
+        }
+    }
+}
+
+/** Writes one file per table into `directory`, named after the table */
+pub fn export<'a>(store: &impl DatStoreImpl<'a>, directory: &Path, tables: &[&str], format: Columnar) -> io::Result<()> {
+    std::fs::create_dir_all(directory)?;
+    for table in tables {
+        let Some(spec) = store.spec(table) else {
+            warn!("Skipping {}, there is no specification for it", table);
+            continue;
+        };
+        let Some(file) = store.file_by_filename(table) else {
+            warn!("Skipping {}, not found in the bundles", table);
+            continue;
+        };
+        info!("Exporting {} rows from {}", file.rows_count, table);
+        let batch = table_batch(spec, &file)?;
+        let path = directory.join(format!("{}.{}", spec.file_name, format.extension()));
+        write(&mut File::create(path)?, &batch, format)?;
+    }
+    Ok(())
+}
+
+/** Writes a query result, a stream of objects where each key becomes a column */
+pub fn write_values<W: Write>(out: &mut W, value: &Value, format: Columnar) -> io::Result<()> {
+    let table = table(value)?;
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (i, key) in table.keys.into_iter().enumerate() {
+        let values: Vec<Value> = table.rows.iter().map(|row| row[i].cloned().unwrap_or(Value::Empty)).collect();
+        let data_type = infer(&values);
+        let name = match key {
+            Value::Str(name) => name.to_string(),
+            key => json(key),
+        };
+        columns.push(array(&data_type, &values)?);
+        fields.push(Field::new(name, data_type, true));
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(io::Error::other)?;
+    write(out, &batch, format)
+}
+
+fn write<W: Write>(out: &mut W, batch: &RecordBatch, format: Columnar) -> io::Result<()> {
+    match format {
+        Columnar::Parquet => {
+            // the parquet writer needs to own a Send writer
+            let mut buffer = Vec::new();
+            let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), None).map_err(io::Error::other)?;
+            writer.write(batch).map_err(io::Error::other)?;
+            writer.close().map_err(io::Error::other)?;
+            out.write_all(&buffer)
+        }
+        Columnar::ArrowIpc => {
+            let mut writer = FileWriter::try_new(out, &batch.schema()).map_err(io::Error::other)?;
+            writer.write(batch).map_err(io::Error::other)?;
+            writer.finish().map_err(io::Error::other)
+        }
+    }
+}
+
+fn table_batch(spec: &FileSpec, file: &DatFile) -> io::Result<RecordBatch> {
+    let rows = 0..file.rows_count as u64;
+    let mut fields = vec![Field::new(INDEX_COLUMN, DataType::UInt64, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt64Array::from_iter_values(rows.clone()))];
+    for (field, name) in spec.file_fields.iter().zip(column_names(spec)) {
+        let data_type = field_type(field);
+        let values: Vec<Value> = rows.clone().map(|row| file.read_field(row, field)).collect();
+        columns.push(array(&data_type, &values)?);
+        fields.push(Field::new(name, data_type, true));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(io::Error::other)
+}
+
+/** Arrow type of a dat field, foreign keys are row indices */
+fn field_type(field: &FieldSpec) -> DataType {
+    if field.enum_name.is_some() {
+        return DataType::Utf8;
+    }
+    type_of(&field.field_type)
+}
+
+fn type_of(field_type: &str) -> DataType {
+    match field_type.split_once('|') {
+        Some(("list", inner)) => DataType::List(Arc::new(Field::new_list_field(type_of(inner), true))),
+        Some(("ref", _)) => DataType::Utf8,
+        _ => match field_type {
+            "bool" => DataType::Boolean,
+            "u8" => DataType::UInt8,
+            "u16" => DataType::UInt16,
+            "i16" => DataType::Int16,
+            "u32" => DataType::UInt32,
+            "i32" => DataType::Int32,
+            "u64" | "ptr" => DataType::UInt64,
+            "i64" => DataType::Int64,
+            "f32" => DataType::Float32,
+            "string" | "path" => DataType::Utf8,
+            _ => DataType::Null,
+        },
+    }
+}
+
+/** Column type of query results, anything that isn't uniform is kept as JSON text */
+fn infer(values: &[Value]) -> DataType {
+    let present: Vec<&Value> = values.iter().filter(|value| **value != Value::Empty).collect();
+    let integer = |value: &&Value| matches!(value, Value::Byte(_) | Value::U64(_) | Value::I64(_));
+    if present.is_empty() {
+        DataType::Null
+    } else if present.iter().all(|value| matches!(value, Value::Bool(_))) {
+        DataType::Boolean
+    } else if present.iter().all(integer) {
+        match present.iter().any(|value| matches!(value, Value::U64(v) if *v > i64::MAX as u64)) {
+            true => DataType::UInt64,
+            false => DataType::Int64,
+        }
+    } else if present.iter().all(|value| integer(value) || matches!(value, Value::F32(_) | Value::F64(_))) {
+        DataType::Float64
+    } else if present.iter().all(|value| matches!(value, Value::List(_))) {
+        let elements: Vec<Value> = present.iter()
+            .flat_map(|value| match value {
+                Value::List(elements) => elements.clone(),
+                _ => vec![],
+            })
+            .collect();
+        DataType::List(Arc::new(Field::new_list_field(infer(&elements), true)))
+    } else {
+        DataType::Utf8
+    }
+}
+
+fn array(data_type: &DataType, values: &[Value]) -> io::Result<ArrayRef> {
+    let unsigned = |value: &Value| match value {
+        Value::Byte(v) => Some(*v as u64),
+        Value::U64(v) => Some(*v),
+        Value::I64(v) => u64::try_from(*v).ok(),
+        _ => None,
+    };
+    let signed = |value: &Value| match value {
+        Value::Byte(v) => Some(*v as i64),
+        Value::U64(v) => i64::try_from(*v).ok(),
+        Value::I64(v) => Some(*v),
+        _ => None,
+    };
+    let float = |value: &Value| match value {
+        Value::F32(v) => Some(*v as f64),
+        Value::F64(v) => Some(*v),
+        value => signed(value).map(|v| v as f64),
+    };
+
+    let array: ArrayRef = match data_type {
+        DataType::Null => Arc::new(NullArray::new(values.len())),
+        DataType::Boolean => Arc::new(values.iter().map(|value| match value {
+            Value::Bool(v) => Some(*v),
+            _ => None,
+        }).collect::<BooleanArray>()),
+        DataType::UInt8 => Arc::new(values.iter().map(|v| unsigned(v).and_then(|v| u8::try_from(v).ok())).collect::<UInt8Array>()),
+        DataType::UInt16 => Arc::new(values.iter().map(|v| unsigned(v).and_then(|v| u16::try_from(v).ok())).collect::<UInt16Array>()),
+        DataType::UInt32 => Arc::new(values.iter().map(|v| unsigned(v).and_then(|v| u32::try_from(v).ok())).collect::<UInt32Array>()),
+        DataType::UInt64 => Arc::new(values.iter().map(unsigned).collect::<UInt64Array>()),
+        DataType::Int16 => Arc::new(values.iter().map(|v| signed(v).and_then(|v| i16::try_from(v).ok())).collect::<Int16Array>()),
+        DataType::Int32 => Arc::new(values.iter().map(|v| signed(v).and_then(|v| i32::try_from(v).ok())).collect::<Int32Array>()),
+        DataType::Int64 => Arc::new(values.iter().map(signed).collect::<Int64Array>()),
+        DataType::Float32 => Arc::new(values.iter().map(|v| float(v).map(|v| v as f32)).collect::<Float32Array>()),
+        DataType::Float64 => Arc::new(values.iter().map(float).collect::<Float64Array>()),
+        DataType::Utf8 => Arc::new(values.iter().map(|value| match value {
+            Value::Empty => None,
+            Value::Str(text) => Some(text.to_string()),
+            value => Some(json(value)),
+        }).collect::<StringArray>()),
+        DataType::List(field) => {
+            let lists: Vec<Option<&[Value]>> = values.iter().map(|value| match value {
+                Value::List(elements) => Some(elements.as_slice()),
+                _ => None,
+            }).collect();
+            let elements: Vec<Value> = lists.iter().flatten().flat_map(|elements| elements.to_vec()).collect();
+            let offsets = OffsetBuffer::from_lengths(lists.iter().map(|list| list.map_or(0, <[Value]>::len)));
+            let nulls = NullBuffer::from_iter(lists.iter().map(Option::is_some));
+            let elements = array(field.data_type(), &elements)?;
+            Arc::new(ListArray::try_new(field.clone(), offsets, elements, Some(nulls)).map_err(io::Error::other)?)
+        }
+        unexpected => return Err(io::Error::other(format!("Unsupported column type {}", unexpected))),
+    };
+    Ok(array)
+}
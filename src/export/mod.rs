@@ -0,0 +1,21 @@
+use std::collections::HashSet;
+
+use crate::dat::specification::FileSpec;
+
+pub mod arrow;
+pub mod passive_tree;
+pub mod sqlite;
+
+/** Row index of every exported table, foreign keys refer to it */
+pub const INDEX_COLUMN: &str = "_index";
+
+// specifications can contain the same field name more than once
+fn column_names(spec: &FileSpec) -> Vec<String> {
+    let mut seen = HashSet::from([INDEX_COLUMN.to_string()]);
+    spec.file_fields.iter().enumerate()
+        .map(|(i, field)| match seen.insert(field.field_name.to_lowercase()) {
+            true => field.field_name.to_string(),
+            false => format!("{}_{}", field.field_name, i),
+        })
+        .collect()
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use log::*;
+
+use crate::dat::{DatReader, DatStoreImpl};
+use crate::dat::file::DatFile;
+use crate::dat::passive_graph::{PassiveGraph, Passive};
+use crate::dat::specification::FileSpec;
+use crate::dat::stat_description::{StatDescriptions, STAT_DESCRIPTIONS};
+use crate::traversal::value::Value;
+
+const PASSIVE_STAT_DESCRIPTIONS: &str = "Metadata/StatDescriptions/passive_skill_stat_descriptions.txt";
+const SKILLS_PER_ORBIT: [u32; 7] = [1, 6, 16, 16, 40, 72, 72];
+const ORBIT_RADII: [f32; 7] = [0.0, 82.0, 162.0, 335.0, 493.0, 662.0, 846.0];
+// orbits with 16 passives aren't evenly spaced
+const ANGLES_16: [f32; 16] = [0.0, 30.0, 45.0, 60.0, 90.0, 120.0, 135.0, 150.0, 180.0, 210.0, 225.0, 240.0, 270.0, 300.0, 315.0, 330.0];
+
+/** The tree in the layout of the official skill tree data, the graph is joined with PassiveSkills and its stat text */
+pub fn tree(store: &DatReader, graph: &str) -> Result<Value, String> {
+    let bytes = store.file_bytes(graph).ok_or_else(|| format!("Failed to read {}", graph))?;
+    let graph = PassiveGraph::parse(&bytes)?;
+    let tables = Tables::read(store)?;
+
+    let read = |path: &str| store.file_bytes(path);
+    let descriptions = StatDescriptions::load(&read, PASSIVE_STAT_DESCRIPTIONS, store.language())
+        .or_else(|_| StatDescriptions::load(&read, STAT_DESCRIPTIONS, store.language()))
+        .inspect_err(|error| warn!("Stats are listed by id, there is no stat text: {}", error))
+        .ok();
+
+    let mut incoming: HashMap<u32, Vec<u32>> = HashMap::new();
+    for passive in graph.groups.iter().flat_map(|group| &group.passives) {
+        for connection in &passive.connections {
+            incoming.entry(connection.id).or_default().push(passive.id);
+        }
+    }
+    let ids = |ids: &[u32]| Value::List(ids.iter().map(|id| Value::Str(id.to_string())).collect());
+
+    let mut nodes = vec![(String::from("root"), Value::object(vec![("out", ids(&graph.roots)), ("in", ids(&[]))]))];
+    let mut groups = Vec::new();
+    let mut jewel_slots = Vec::new();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for (i, group) in graph.groups.iter().enumerate() {
+        let mut orbits: Vec<u32> = group.passives.iter().map(|passive| passive.orbit).collect();
+        orbits.sort();
+        orbits.dedup();
+        groups.push((format!("{}", i + 1), Value::object(vec![
+            ("x", Value::F32(group.x)),
+            ("y", Value::F32(group.y)),
+            ("orbits", Value::List(orbits.into_iter().map(|orbit| Value::U64(orbit as u64)).collect())),
+            ("nodes", ids(&group.passives.iter().map(|passive| passive.id).collect::<Vec<_>>())),
+        ])));
+
+        for passive in &group.passives {
+            let (x, y) = position(group.x, group.y, passive);
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+
+            let mut entries = match tables.passives.get(&passive.id) {
+                Some(row) => tables.node(*row, descriptions.as_ref()),
+                None => {
+                    warn!("Passive {} in the graph has no row in PassiveSkills", passive.id);
+                    vec![("skill", Value::U64(passive.id as u64))]
+                }
+            };
+            if entries.iter().any(|(key, _)| *key == "isJewelSocket") {
+                jewel_slots.push(passive.id);
+            }
+            let outgoing: Vec<u32> = passive.connections.iter().map(|connection| connection.id).collect();
+            entries.extend([
+                ("group", Value::U64(i as u64 + 1)),
+                ("orbit", Value::U64(passive.orbit as u64)),
+                ("orbitIndex", Value::U64(passive.orbit_index as u64)),
+                ("out", ids(&outgoing)),
+                ("in", ids(incoming.get(&passive.id).map_or(&[], Vec::as_slice))),
+            ]);
+            nodes.push((passive.id.to_string(), Value::object(entries)));
+        }
+    }
+    if graph.groups.is_empty() {
+        (min_x, min_y, max_x, max_y) = (0.0, 0.0, 0.0, 0.0);
+    }
+
+    let numbers = |values: &[f32]| Value::List(values.iter().map(|value| Value::F32(*value)).collect());
+    let constants = Value::object(vec![
+        ("classes", Value::object(["StrDexIntClass", "StrClass", "DexClass", "IntClass", "StrDexClass", "StrIntClass", "DexIntClass"]
+            .iter().enumerate().map(|(i, class)| (*class, Value::U64(i as u64))))),
+        ("characterAttributes", Value::object(vec![("Strength", Value::U64(0)), ("Dexterity", Value::U64(1)), ("Intelligence", Value::U64(2))])),
+        ("PSSCentreInnerRadius", Value::U64(130)),
+        ("skillsPerOrbit", Value::List(SKILLS_PER_ORBIT.iter().map(|count| Value::U64(*count as u64)).collect())),
+        ("orbitRadii", numbers(&ORBIT_RADII)),
+    ]);
+    Ok(Value::object(vec![
+        ("tree", Value::Str(String::from("Default"))),
+        ("classes", tables.classes()),
+        ("groups", Value::object(groups)),
+        ("nodes", Value::object(nodes)),
+        ("jewelSlots", Value::List(jewel_slots.into_iter().map(|id| Value::U64(id as u64)).collect())),
+        ("min_x", Value::F32(min_x.round())),
+        ("min_y", Value::F32(min_y.round())),
+        ("max_x", Value::F32(max_x.round())),
+        ("max_y", Value::F32(max_y.round())),
+        ("constants", constants),
+    ]))
+}
+
+// orbit index zero is straight up from the centre of the group, going clockwise
+fn position(x: f32, y: f32, passive: &Passive) -> (f32, f32) {
+    let orbit = (passive.orbit as usize).min(ORBIT_RADII.len() - 1);
+    let angle = match SKILLS_PER_ORBIT[orbit] {
+        16 => ANGLES_16[passive.orbit_index as usize % 16].to_radians(),
+        count => 2.0 * PI * passive.orbit_index as f32 / count as f32,
+    };
+    (x + ORBIT_RADII[orbit] * angle.sin(), y - ORBIT_RADII[orbit] * angle.cos())
+}
+
+/** A table with its schema, fields the schema doesn't have read as null */
+struct Table {
+    spec: FileSpec,
+    file: DatFile,
+}
+
+impl Table {
+    fn read(store: &DatReader, name: &str) -> Option<Table> {
+        let spec = store.spec(name)?.clone();
+        let file = store.file_by_filename(name)?;
+        Some(Table { spec, file })
+    }
+
+    fn get(&self, row: u64, field: &str) -> Value {
+        match self.spec.file_fields.iter().find(|spec| spec.field_name == field) {
+            Some(spec) if row < self.file.rows_count as u64 => self.file.read_field(row, spec),
+            _ => Value::Empty,
+        }
+    }
+
+    // rows a foreign key or a list of them points at
+    fn rows(value: Value) -> Vec<u64> {
+        match value {
+            Value::U64(row) => vec![row],
+            Value::List(values) => values.into_iter().filter_map(|value| match value {
+                Value::U64(row) => Some(row),
+                _ => None,
+            }).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+struct Tables {
+    passive_skills: Table,
+    passives: HashMap<u32, u64>,
+    stats: Option<Table>,
+    characters: Option<Table>,
+    ascendancies: Option<Table>,
+    reminders: Option<Table>,
+    mastery_groups: Option<Table>,
+    mastery_effects: Option<Table>,
+}
+
+impl Tables {
+    fn read(store: &DatReader) -> Result<Tables, String> {
+        let passive_skills = Table::read(store, "PassiveSkills").ok_or("PassiveSkills is missing from the game files or the schema")?;
+        let mut passives = HashMap::new();
+        for row in 0..passive_skills.file.rows_count as u64 {
+            if let Value::U64(id) = passive_skills.get(row, "PassiveSkillGraphId") {
+                passives.entry(id as u32).or_insert(row);
+            }
+        }
+        Ok(Tables {
+            passive_skills,
+            passives,
+            stats: Table::read(store, "Stats"),
+            characters: Table::read(store, "Characters"),
+            ascendancies: Table::read(store, "Ascendancy"),
+            reminders: Table::read(store, "ReminderText"),
+            mastery_groups: Table::read(store, "PassiveSkillMasteryGroups"),
+            mastery_effects: Table::read(store, "PassiveSkillMasteryEffects"),
+        })
+    }
+
+    fn node(&self, row: u64, descriptions: Option<&StatDescriptions>) -> Vec<(&'static str, Value)> {
+        let passive = &self.passive_skills;
+        let mut entries = vec![
+            ("skill", passive.get(row, "PassiveSkillGraphId")),
+            ("name", passive.get(row, "Name")),
+            ("icon", passive.get(row, "Icon_DDSFile")),
+        ];
+        // flags are only there when they are set, like in the official data
+        for (key, field) in [
+            ("isKeystone", "IsKeystone"),
+            ("isNotable", "IsNotable"),
+            ("isJewelSocket", "IsJewelSocket"),
+            ("isJustIcon", "IsJustIcon"),
+            ("isMultipleChoice", "IsMultipleChoice"),
+            ("isMultipleChoiceOption", "IsMultipleChoiceOption"),
+            ("isBlighted", "IsAnointmentOnly"),
+            ("isProxy", "IsProxyPassive"),
+            ("isAscendancyStart", "IsAscendancyStartingNode"),
+        ] {
+            if passive.get(row, field) == Value::Bool(true) {
+                entries.push((key, Value::Bool(true)));
+            }
+        }
+
+        let ascendancy = Table::rows(passive.get(row, "AscendancyKey")).first()
+            .zip(self.ascendancies.as_ref())
+            .map(|(ascendancy, table)| table.get(*ascendancy, "Name"));
+        if let Some(name) = ascendancy {
+            entries.push(("ascendancyName", name));
+        }
+        let class = Table::rows(passive.get(row, "Characters")).first()
+            .zip(self.characters.as_ref())
+            .map(|(character, table)| table.get(*character, "IntegerId"));
+        if let Some(class) = class {
+            entries.push(("classStartIndex", class));
+        }
+        if let Value::I64(points @ 1..) = passive.get(row, "SkillPointsGranted") {
+            entries.push(("grantedPassivePoints", Value::I64(points)));
+        }
+
+        let values = (1..=5).map(|i| passive.get(row, &format!("Stat{}Value", i))).collect();
+        entries.push(("stats", self.stat_text(passive.get(row, "Stats"), values, descriptions)));
+
+        if let Some(group) = Table::rows(passive.get(row, "MasteryGroup")).first() {
+            entries.push(("isMastery", Value::Bool(true)));
+            entries.extend(self.mastery(*group, descriptions));
+        }
+        let reminders: Vec<Value> = Table::rows(passive.get(row, "ReminderStrings")).into_iter()
+            .filter_map(|reminder| Some(self.reminders.as_ref()?.get(reminder, "Text")))
+            .collect();
+        if !reminders.is_empty() {
+            entries.push(("reminderText", Value::List(reminders)));
+        }
+        if let Value::Str(flavour) = passive.get(row, "FlavourText") {
+            if !flavour.is_empty() {
+                entries.push(("flavourText", Value::List(flavour.lines().map(|line| Value::Str(line.to_string())).collect())));
+            }
+        }
+        entries
+    }
+
+    fn mastery(&self, group: u64, descriptions: Option<&StatDescriptions>) -> Vec<(&'static str, Value)> {
+        let Some(groups) = &self.mastery_groups else { return vec![] };
+        let effects = Table::rows(groups.get(group, "MasteryEffects")).into_iter()
+            .filter_map(|effect| {
+                let table = self.mastery_effects.as_ref()?;
+                let values = (1..=3).map(|i| table.get(effect, &format!("Stat{}Value", i))).collect();
+                Some(Value::object(vec![
+                    ("effect", table.get(effect, "HASH16")),
+                    ("stats", self.stat_text(table.get(effect, "Stats"), values, descriptions)),
+                ]))
+            })
+            .collect();
+        vec![
+            ("inactiveIcon", groups.get(group, "InactiveIcon")),
+            ("activeIcon", groups.get(group, "ActiveIcon")),
+            ("activeEffectImage", groups.get(group, "ActiveEffectImage")),
+            ("masteryEffects", Value::List(effects)),
+        ]
+    }
+
+    // lines of stat text, `id value` for each stat when there are no descriptions
+    fn stat_text(&self, stats: Value, values: Vec<Value>, descriptions: Option<&StatDescriptions>) -> Value {
+        let stats: Vec<(String, i64, i64)> = Table::rows(stats).into_iter().zip(values)
+            .filter_map(|(stat, value)| {
+                let Value::Str(id) = self.stats.as_ref()?.get(stat, "Id") else { return None };
+                let value = match value {
+                    Value::I64(value) => value,
+                    Value::U64(value) => value as i64,
+                    _ => 0,
+                };
+                Some((id, value, value))
+            })
+            .collect();
+        let lines = match descriptions {
+            Some(descriptions) => descriptions.describe(&stats),
+            None => stats.into_iter().map(|(id, value, _)| format!("{} {}", id, value)).collect(),
+        };
+        Value::List(lines.into_iter().map(Value::Str).collect())
+    }
+
+    fn classes(&self) -> Value {
+        let Some(characters) = &self.characters else { return Value::List(vec![]) };
+        let classes = (0..characters.file.rows_count as u64)
+            .map(|character| {
+                let ascendancies = self.ascendancies.iter()
+                    .flat_map(|table| (0..table.file.rows_count as u64)
+                        .filter(move |row| table.get(*row, "Character") == Value::U64(character))
+                        .map(move |row| Value::object(vec![("id", table.get(row, "Id")), ("name", table.get(row, "Name"))])))
+                    .collect();
+                Value::object(vec![
+                    ("name", characters.get(character, "Name")),
+                    ("base_str", characters.get(character, "BaseStrength")),
+                    ("base_dex", characters.get(character, "BaseDexterity")),
+                    ("base_int", characters.get(character, "BaseIntelligence")),
+                    ("ascendancies", Value::List(ascendancies)),
+                ])
+            })
+            .collect();
+        Value::List(classes)
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use log::*;
+use rusqlite::{Connection, params_from_iter, Transaction};
+use rusqlite::types::Value as SqlValue;
+
+use crate::dat::DatStoreImpl;
+use crate::dat::file::DatFile;
+use crate::dat::specification::{FieldSpec, FieldSpecImpl, FileSpec};
+use crate::export::{column_names, INDEX_COLUMN};
+use crate::traversal::format::json;
+use crate::traversal::value::Value;
+
+/** Writes tables to an SQLite database, existing tables with the same name are replaced */
+pub fn export<'a>(store: &impl DatStoreImpl<'a>, path: &Path, tables: &[&str]) -> rusqlite::Result<()> {
+    let mut connection = Connection::open(path)?;
+    // tables are written one at a time and can reference tables that are not exported
+    connection.pragma_update(None, "foreign_keys", false)?;
+    let transaction = connection.transaction()?;
+    for table in tables {
+        let Some(spec) = store.spec(table) else {
+            warn!("Skipping {}, there is no specification for it", table);
+            continue;
+        };
+        let Some(file) = store.file_by_filename(table) else {
+            warn!("Skipping {}, not found in the bundles", table);
+            continue;
+        };
+        info!("Exporting {} rows from {}", file.rows_count, table);
+        export_table(&transaction, spec, &file)?;
+    }
+    transaction.commit()
+}
+
+fn export_table(transaction: &Transaction, spec: &FileSpec, file: &DatFile) -> rusqlite::Result<()> {
+    let columns = column_names(spec);
+    let definitions: Vec<String> = spec.file_fields.iter().zip(&columns)
+        .map(|(field, column)| format!("{} {}", quote(column), column_type(field)))
+        .collect();
+
+    transaction.execute(&format!("DROP TABLE IF EXISTS {}", quote(&spec.file_name)), [])?;
+    transaction.execute(&format!(
+        "CREATE TABLE {} ({} INTEGER PRIMARY KEY, {})",
+        quote(&spec.file_name), quote(INDEX_COLUMN), definitions.join(", ")
+    ), [])?;
+
+    // lists of foreign keys also get a child table so they can be joined
+    let mut key_lists = Vec::new();
+    for (i, (field, column)) in spec.file_fields.iter().zip(&columns).enumerate() {
+        if !field.is_foreign_key() || !field.field_type.starts_with("list|") {
+            continue;
+        }
+        let table = format!("{}_{}", spec.file_name, column);
+        transaction.execute(&format!("DROP TABLE IF EXISTS {}", quote(&table)), [])?;
+        transaction.execute(&format!(
+            "CREATE TABLE {} ({} INTEGER REFERENCES {}({}), \"position\" INTEGER, \"value\" INTEGER{})",
+            quote(&table), quote(INDEX_COLUMN), quote(&spec.file_name), quote(INDEX_COLUMN), references(field)
+        ), [])?;
+        key_lists.push((i, transaction.prepare(&format!("INSERT INTO {} VALUES (?, ?, ?)", quote(&table)))?));
+    }
+
+    let placeholders = vec!["?"; columns.len() + 1].join(", ");
+    let mut insert_row = transaction.prepare(&format!("INSERT INTO {} VALUES ({})", quote(&spec.file_name), placeholders))?;
+    for row in 0..file.rows_count as u64 {
+        let values: Vec<Value> = spec.file_fields.iter()
+            .map(|field| file.read_field(row, field))
+            .collect();
+
+        for (i, insert_key) in key_lists.iter_mut() {
+            let Value::List(keys) = &values[*i] else { continue };
+            for (position, key) in keys.iter().enumerate() {
+                insert_key.execute(params_from_iter([SqlValue::Integer(row as i64), SqlValue::Integer(position as i64), to_sql(key)]))?;
+            }
+        }
+
+        let row_values = std::iter::once(SqlValue::Integer(row as i64)).chain(values.iter().map(to_sql));
+        insert_row.execute(params_from_iter(row_values))?;
+    }
+    Ok(())
+}
+
+fn column_type(field: &FieldSpec) -> String {
+    if field.enum_name.is_some() {
+        return String::from("TEXT");
+    }
+    if field.is_foreign_key() && !field.field_type.starts_with("list|") {
+        return format!("INTEGER{}", references(field));
+    }
+    match field.field_type.as_str() {
+        "bool" | "u8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "ptr" => "INTEGER",
+        "f32" => "REAL",
+        "_" => "BLOB",
+        _ => "TEXT", // strings, paths and lists as JSON
+    }.to_string()
+}
+
+fn references(field: &FieldSpec) -> String {
+    match &field.file_name {
+        Some(table) => format!(" REFERENCES {}({})", quote(table), quote(INDEX_COLUMN)),
+        None => String::new(),
+    }
+}
+
+fn to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Empty => SqlValue::Null,
+        Value::Bool(value) => SqlValue::Integer(*value as i64),
+        Value::Byte(value) => SqlValue::Integer(*value as i64),
+        Value::I64(value) => SqlValue::Integer(*value),
+        Value::U64(value) => match i64::try_from(*value) {
+            Ok(value) => SqlValue::Integer(value),
+            Err(_) => SqlValue::Text(value.to_string()),
+        },
+        Value::F32(value) => SqlValue::Real(*value as f64),
+        Value::F64(value) => SqlValue::Real(*value),
+        Value::Str(text) => SqlValue::Text(text.to_string()),
+        value => SqlValue::Text(json(value)),
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
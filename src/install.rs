@@ -0,0 +1,90 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+const GAMES: [&str; 2] = ["Path of Exile", "Path of Exile 2"];
+
+/** The user's home folder, installs below it are found */
+pub fn home() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+/** Folders a game install is looked for in, in order */
+pub fn candidates(home: Option<&Path>) -> Vec<PathBuf> {
+    let mut steam_roots = vec![PathBuf::from("C:/Program Files (x86)/Steam"), PathBuf::from("C:/Program Files/Steam")];
+    let mut drives = vec![PathBuf::from("C:/")];
+    let mut standalone = Vec::new();
+    if let Some(home) = home {
+        steam_roots.extend([
+            ".steam/steam",
+            ".local/share/Steam",
+            ".var/app/com.valvesoftware.Steam/.local/share/Steam", // flatpak
+            "snap/steam/common/.local/share/Steam",
+            "Library/Application Support/Steam", // macOS
+        ].map(|root| home.join(root)));
+        drives.push(home.join(".wine/drive_c"));
+        // Lutris creates a prefix per game
+        drives.extend(subdirectories(&home.join("Games")).into_iter().map(|prefix| prefix.join("drive_c")));
+        // the macOS client downloads the game next to its other caches
+        standalone.push(home.join("Library/Caches/com.GGG.PathOfExile/Rockets"));
+    }
+
+    let mut libraries: Vec<PathBuf> = Vec::new();
+    for root in steam_roots {
+        let folders = std::fs::read_to_string(root.join("steamapps/libraryfolders.vdf")).unwrap_or_default();
+        libraries.push(root);
+        libraries.extend(steam_libraries(&folders));
+    }
+    // the standalone client added to Steam runs in a Proton prefix
+    for library in &libraries {
+        drives.extend(subdirectories(&library.join("steamapps/compatdata")).into_iter().map(|prefix| prefix.join("pfx/drive_c")));
+    }
+
+    let mut candidates = vec![PathBuf::from(".")];
+    candidates.extend(standalone);
+    for drive in drives {
+        for program_files in ["Program Files (x86)", "Program Files"] {
+            candidates.extend(GAMES.map(|game| drive.join(program_files).join("Grinding Gear Games").join(game)));
+        }
+    }
+    for library in libraries {
+        candidates.extend(GAMES.map(|game| library.join("steamapps/common").join(game)));
+    }
+
+    let mut unique = Vec::new();
+    for candidate in candidates {
+        if !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique
+}
+
+/** Library folders listed in Steam's `libraryfolders.vdf`, both the current and the older format */
+pub fn steam_libraries(vdf: &str) -> Vec<PathBuf> {
+    vdf.lines()
+        .filter_map(|line| {
+            let mut quoted = line.split('"').skip(1).step_by(2);
+            let (key, value) = (quoted.next()?, quoted.next()?);
+            let is_library = key == "path" || (key.chars().all(|c| c.is_ascii_digit()) && value.contains(['/', '\\']));
+            is_library.then(|| PathBuf::from(value.replace("\\\\", "\\")))
+        })
+        .collect()
+}
+
+/** A folder with a game install, or the path to a Content.ggpk */
+pub fn is_install(path: &Path) -> bool {
+    let is_file = path.exists() && path.is_file();
+    let has_ggpk = path.join("Content.ggpk").exists();
+    let has_index = path.join("Bundles2/_.index.bin").exists();
+    is_file || has_ggpk || has_index
+}
+
+fn subdirectories(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(path) else { return vec![] };
+    let mut directories: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    directories.sort();
+    directories
+}
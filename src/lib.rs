@@ -7,6 +7,15 @@ use crate::dat::DatReader;
 use crate::query::Term;
 use crate::traversal::value::Value;
 
+pub mod bundle;
+pub mod cache;
+pub mod config;
 pub mod dat;
+pub mod dds;
+pub mod diff;
+pub mod export;
+pub mod install;
+pub mod output;
 pub mod query;
+pub mod repl;
 pub mod traversal;
\ No newline at end of file
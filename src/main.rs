@@ -1,8 +1,7 @@
-#[macro_use]
-extern crate pest_derive;
-extern crate core;
-
-use std::{env, process};
+use std::{env, io, process};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
@@ -11,14 +10,25 @@ use log::*;
 use poe_bundle::BundleReader;
 use simplelog::*;
 
-use crate::dat::DatReader;
-use crate::query::Term;
-use crate::traversal::{StaticContext, QueryProcessor};
-use crate::traversal::value::Value;
-
-mod dat;
-mod query;
-mod traversal;
+use poe_query_lib::bundle;
+use poe_query_lib::cache::CachedBundles;
+use poe_query_lib::config::Config;
+use poe_query_lib::dat;
+use poe_query_lib::dat::{DatReader, DatStoreImpl};
+use poe_query_lib::dat::passive_graph::PASSIVE_SKILL_GRAPH;
+use poe_query_lib::diff;
+use poe_query_lib::dat::specification::FileSpec;
+use poe_query_lib::export;
+use poe_query_lib::export::arrow::Columnar;
+use poe_query_lib::install;
+use poe_query_lib::output;
+use poe_query_lib::output::{Format, OutputOptions};
+use poe_query_lib::output::encoding::Encoding;
+use poe_query_lib::query;
+use poe_query_lib::repl;
+use poe_query_lib::query::Term;
+use poe_query_lib::traversal::{StaticContext, QueryProcessor};
+use poe_query_lib::traversal::value::Value;
 
 #[derive(clap::Parser)]
 #[command(name = "PoE Query")]
@@ -26,67 +36,493 @@ mod traversal;
 #[command(version = env ! ("CARGO_PKG_VERSION"))]
 #[command(about = "Query and transform data from Path of Exile", long_about = None)]
 struct Args {
-    #[arg(short, long, value_name = "INSTALL_DIR")]
+    #[arg(short, long, value_name = "INSTALL_DIR", global = true)]
     path: Option<PathBuf>,
 
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
     verbose: u8,
 
-    #[arg(short, long, default_value_t = String::from("English"))]
-    language: String,
+    /// Language of the game data, English unless set in the config
+    #[arg(short, long, global = true)]
+    language: Option<String>,
+
+    /// Folder with the dat schema, next to the executable unless set in the config
+    #[arg(long, value_name = "SCHEMA_DIR", global = true)]
+    schema: Option<PathBuf>,
+
+    /// Keep the bundle index and decompressed files in a folder, they are read again when the game patches
+    #[arg(long, value_name = "CACHE_DIR", global = true)]
+    cache_dir: Option<PathBuf>,
+
+    /// Compact instead of pretty printed JSON
+    #[arg(short, long)]
+    compact: bool,
+
+    /// Write strings without quotes
+    #[arg(short, long)]
+    raw_output: bool,
+
+    /// Like --raw-output but without a newline after each value
+    #[arg(short, long)]
+    join_output: bool,
+
+    /// Indent with tabs
+    #[arg(long)]
+    tab: bool,
+
+    /// Number of spaces to indent with, 0 is compact
+    #[arg(long, value_name = "n", default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=7))]
+    indent: u8,
+
+    /// Compact JSON with one streamed value or list element per line
+    #[arg(long)]
+    ndjson: bool,
+
+    /// Write a stream of objects as CSV with a header line
+    #[arg(long)]
+    csv: bool,
+
+    /// Encoding of the query result, toml expects a single object and parquet or arrow a stream of objects
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    output_format: OutputFormat,
+
+    /// Don't read JSON input, the query starts from the dat tables
+    #[arg(short, long)]
+    null_input: bool,
+
+    /// Read JSON from stdin even when the query uses a dat table, same as the file `-`
+    #[arg(long)]
+    input: bool,
+
+    /// Read every JSON input into one array and run the query once
+    #[arg(short, long)]
+    slurp: bool,
+
+    /// Bind $name to a string
+    #[arg(long, num_args = 2, value_names = ["name", "value"])]
+    arg: Vec<String>,
+
+    /// Bind $name to a JSON value
+    #[arg(long, num_args = 2, value_names = ["name", "json"])]
+    argjson: Vec<String>,
+
+    /// Bind $name to an array of the JSON values in a file
+    #[arg(long, num_args = 2, value_names = ["name", "file"])]
+    slurpfile: Vec<String>,
+
+    /// Read the query from a file, `include "name";` is relative to it
+    #[arg(short, long, value_name = "FILE")]
+    from_file: Option<PathBuf>,
+
+    /// Remaining arguments are strings in $ARGS.positional instead of input files
+    #[arg(long = "args")]
+    positional_args: bool,
+
+    /// Write the files in the bundles that strings in the result refer to, textures are decoded to PNG
+    #[arg(long, value_name = "DIR")]
+    extract_files: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    query: Option<String>,
+
+    /// JSON files to use as input instead of stdin
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Export whole tables instead of running a query
+    Export {
+        #[arg(value_enum)]
+        format: ExportFormat,
+
+        /// File to write to, a directory for parquet and arrow
+        output: PathBuf,
+
+        /// Tables to export, all of them when omitted
+        tables: Vec<String>,
+    },
+    /// Run queries interactively, the bundles are only indexed once
+    Repl,
+    /// List the languages in the install, for --language and localized
+    Languages,
+    /// List files in the bundles matching glob patterns like 'metadata/items/**/*.it', all files when omitted
+    List {
+        patterns: Vec<String>,
+    },
+    /// Write files in the bundles matching glob patterns to a directory, keeping their paths
+    Extract {
+        output: PathBuf,
+
+        #[arg(required = true)]
+        patterns: Vec<String>,
+    },
+    /// Compare the tables of two installs, Bundles2 snapshots or extracted directories
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+
+        /// Tables to compare, all of them when omitted
+        tables: Vec<String>,
+
+        /// Schema for the old version, --schema when omitted
+        #[arg(long)]
+        old_schema: Option<PathBuf>,
+
+        /// Schema for the new version, --schema when omitted
+        #[arg(long)]
+        new_schema: Option<PathBuf>,
+    },
+    /// Export the passive tree in the layout of the official skill tree data, with the stat text of every passive
+    Tree {
+        /// Graph file in the bundles, e.g. the atlas tree in Metadata/AtlasSkillGraphs/AtlasSkillGraph.psg
+        #[arg(long, default_value = PASSIVE_SKILL_GRAPH)]
+        graph: String,
+    },
+    /// Store --path, --language and --schema in the config file so they don't have to be given again
+    Config,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum ExportFormat {
+    Sqlite,
+    Parquet,
+    Arrow,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    Msgpack,
+    Cbor,
+    Parquet,
+    Arrow,
+}
+
+impl OutputFormat {
+    fn is_binary(&self) -> bool {
+        match self {
+            OutputFormat::Json | OutputFormat::Yaml | OutputFormat::Toml => false,
+            OutputFormat::Msgpack | OutputFormat::Cbor | OutputFormat::Parquet | OutputFormat::Arrow => true,
+        }
+    }
+}
 
-    query: String,
+impl Args {
+    fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or("English")
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
     init_logger(args.verbose);
     debug!("Version {:?}", env!("CARGO_PKG_VERSION"));
 
-    let install_path = find_poe_install(args.path);
-    let schema_path = find_schema_path();
+    let config_path = Config::default_path();
+    if let Some(Command::Config) = args.command {
+        save_config(&args, config_path);
+        return;
+    }
+    // flags take precedence over the config
+    let config = config_path.map(|path| Config::read(&path)).transpose().unwrap_or_else(|error| {
+        error!("{}", error);
+        process::exit(-1);
+    }).unwrap_or_default();
+    args.path = args.path.take().or(config.install_path);
+    args.language = args.language.take().or(config.language);
+    args.cache_dir = args.cache_dir.take().or(config.cache_path);
+
+    let install_path = find_poe_install(args.path.clone());
+    let schema_path = find_schema_path(args.schema.clone().or(config.schema_path));
     info!("Using: {:?}", install_path);
     info!("Schemas: {:?}", schema_path);
 
+    match &args.command {
+        Some(Command::Export { format, output, tables }) => {
+            let install_path = require_poe_install(install_path);
+            export_tables(&args, &install_path, &schema_path, format, output, tables)
+        }
+        Some(Command::Repl) => run_repl(&args, &require_poe_install(install_path), &schema_path),
+        Some(Command::Diff { old, new, tables, old_schema, new_schema }) => {
+            let old_schema = old_schema.clone().map_or(schema_path.clone(), PathBuf::into_boxed_path);
+            let new_schema = new_schema.clone().map_or(schema_path.clone(), PathBuf::into_boxed_path);
+            run_diff(&args, (old, &old_schema), (new, &new_schema), tables)
+        }
+        Some(Command::List { patterns }) => {
+            let bundles = Bundles::open(&args, &require_poe_install(install_path));
+            let mut out = BufWriter::new(io::stdout().lock());
+            let written = bundle::matching(bundles.paths(), patterns).iter().try_for_each(|path| writeln!(out, "{}", path));
+            if let Err(error) = written.and_then(|_| out.flush()) {
+                if error.kind() != io::ErrorKind::BrokenPipe {
+                    error!("Failed to write output: {}", error);
+                    process::exit(-1);
+                }
+            }
+        }
+        Some(Command::Extract { output, patterns }) => {
+            let bundles = BundleReader::from_install(&require_poe_install(install_path));
+            let paths = bundle::matching(&bundles.index.paths, patterns);
+            if let Err(error) = bundle::extract(&bundles, &paths, output) {
+                error!("Failed to extract to {}: {}", output.display(), error);
+                process::exit(-1);
+            }
+            info!("Extracted {} files", paths.len());
+        }
+        Some(Command::Tree { graph }) => {
+            let bundles = Bundles::open(&args, &require_poe_install(install_path));
+            let container = bundles.reader(args.language(), &schema_path);
+            match export::passive_tree::tree(&container, graph) {
+                Ok(tree) => {
+                    write_result(&args, &tree);
+                    extract_files(&args, &bundles, &tree);
+                }
+                Err(error) => {
+                    error!("{}", error);
+                    process::exit(-1);
+                }
+            }
+        }
+        Some(Command::Languages) => {
+            let bundles = BundleReader::from_install(&require_poe_install(install_path));
+            dat::languages(&bundles.index.paths).iter().for_each(|language| println!("{}", language));
+        }
+        Some(Command::Config) => unreachable!("handled before reading the config"),
+        None => run_query(&args, install_path, &schema_path),
+    }
+}
+
+fn export_tables(args: &Args, install_path: &Path, schema_path: &Path, format: &ExportFormat, output: &Path, tables: &[String]) {
+    let now = Instant::now();
+    let bundles = Bundles::open(args, install_path);
+    let container = bundles.reader(args.language(), schema_path);
+
+    let mut tables: Vec<&str> = tables.iter().map(String::as_str).collect();
+    if tables.is_empty() {
+        tables = container.exports().into_iter().collect();
+        tables.sort();
+    }
+
+    let exported = match format {
+        ExportFormat::Sqlite => export::sqlite::export(&container, output, &tables).map_err(|error| error.to_string()),
+        ExportFormat::Parquet => export::arrow::export(&container, output, &tables, Columnar::Parquet).map_err(|error| error.to_string()),
+        ExportFormat::Arrow => export::arrow::export(&container, output, &tables, Columnar::ArrowIpc).map_err(|error| error.to_string()),
+    };
+    if let Err(error) = exported {
+        error!("Failed to export to {}: {}", output.display(), error);
+        process::exit(-1);
+    }
+    info!("export spent: {}ms", now.elapsed().as_millis());
+}
+
+fn run_query(args: &Args, install_path: Option<Box<Path>>, schema_path: &Path) {
+    let (query, directory) = match (&args.from_file, &args.query) {
+        (Some(file), _) => match std::fs::read_to_string(file) {
+            Ok(query) => (query, file.parent().unwrap_or(Path::new(".")).to_path_buf()),
+            Err(error) => {
+                error!("Failed to read {}: {}", file.display(), error);
+                process::exit(-1);
+            }
+        },
+        (None, Some(query)) => (query.clone(), PathBuf::from(".")),
+        (None, None) => {
+            error!("No query provided, see --help for usage");
+            process::exit(-1);
+        }
+    };
+    refuse_binary_terminal(args);
+
     // Parse
     let now = Instant::now();
-    let terms = match query::parse_query(&args.query) {
+    let terms = match query::expand_includes(&query, &directory).and_then(|query| query::parse_query(&query)) {
         Ok(t) => t,
         Err(error) => {
             error!("{}", error);
             process::exit(-1);
         },
     };
+    let inputs = read_inputs(args, &terms, schema_path);
+    let variables = variables(args);
     let (parse_query_ms, now) = (now.elapsed().as_millis(), Instant::now());
 
-    // Index bundles
-    let bundles = BundleReader::from_install(&install_path);
-    let container = DatReader::from_install(&args.language, &bundles, &schema_path);
+    // Index bundles, JSON input only needs them when the query uses a dat table or files are extracted
+    let needs_bundles = || args.extract_files.is_some() || references_table(&terms, inputs.as_deref(), schema_path);
+    let install_path = match (install_path, &inputs) {
+        (Some(path), Some(_)) => Some(path).filter(|_| needs_bundles()),
+        (Some(path), None) => Some(path),
+        (None, _) if args.path.is_some() || needs_bundles() => Some(require_poe_install(None)),
+        (None, _) => None,
+    };
+    let bundles = install_path.map(|path| Bundles::open(args, &path));
+    let container = bundles.as_ref().map(|bundles| bundles.reader(args.language(), schema_path));
     let (read_index_ms, now) = (now.elapsed().as_millis(), Instant::now());
 
     // Transform
-    let context = StaticContext::new(&container);
-    let result = StaticContext::process(&context, &terms);
+    let context = match &container {
+        Some(container) => StaticContext::new(container),
+        None => StaticContext::default(),
+    }.with_variables(variables);
+    let result = match inputs {
+        None => context.process(&terms),
+        Some(inputs) => Value::Iterator(inputs.into_iter()
+            .flat_map(|input| match context.process_input(&terms, input) {
+                Value::Iterator(values) => values,
+                value => vec![value],
+            })
+            .collect()),
+    };
     let (query_ms, now) = (now.elapsed().as_millis(), Instant::now());
 
     // Output
-    match result {
-        Value::Iterator(items) => {
-            items.iter().for_each(serialize_and_print);
-        }
-        _ => serialize_and_print(&result)
-    };
+    write_result(args, &result);
     let serialize_ts = now.elapsed().as_millis();
 
+    if let Some(bundles) = &bundles {
+        extract_files(args, bundles, &result);
+    }
+
     info!("parse query: {}ms", parse_query_ms);
     info!("bundle index: {}ms", read_index_ms);
     info!("transform spent: {}ms", query_ms);
     info!("serialize spent: {}ms", serialize_ts);
 }
 
-fn serialize_and_print(value: &Value) {
-    let serialized = serde_json::to_string_pretty(&value).unwrap();
-    println!("{}", serialized);
+fn write_result(args: &Args, result: &Value) {
+    let mut out = BufWriter::new(io::stdout().lock());
+    if let Err(error) = output::write(&mut out, result, &output_options(args)).and_then(|_| out.flush()) {
+        // the reader went away, e.g. piped into head
+        if error.kind() != io::ErrorKind::BrokenPipe {
+            error!("Failed to write output: {}", error);
+            process::exit(-1);
+        }
+    }
+}
+
+fn extract_files(args: &Args, bundles: &Bundles, result: &Value) {
+    let Some(output) = &args.extract_files else { return };
+    match bundle::extract_referenced(bundles.index(), result, output) {
+        Ok(count) => info!("Extracted {} files", count),
+        Err(error) => {
+            error!("Failed to extract to {}: {}", output.display(), error);
+            process::exit(-1);
+        }
+    }
+}
+
+/** Bundles indexed up front, or with --cache-dir only once a file isn't cached */
+enum Bundles {
+    Indexed(BundleReader),
+    Cached(CachedBundles),
+}
+
+impl Bundles {
+    fn open(args: &Args, install_path: &Path) -> Bundles {
+        let Some(cache) = &args.cache_dir else { return Bundles::Indexed(BundleReader::from_install(install_path)) };
+        match CachedBundles::open(install_path, cache) {
+            Ok(cached) => Bundles::Cached(cached),
+            Err(error) => {
+                warn!("Not using the cache in {}: {}", cache.display(), error);
+                Bundles::Indexed(BundleReader::from_install(install_path))
+            }
+        }
+    }
+
+    fn reader<'a>(&'a self, language: &'a str, schema_path: &Path) -> DatReader<'a> {
+        match self {
+            Bundles::Indexed(bundles) => DatReader::from_install(language, bundles, schema_path),
+            Bundles::Cached(cached) => DatReader::from_cache(language, cached, schema_path),
+        }
+    }
+
+    /** Every path in the bundle index, with --cache-dir without indexing the bundles */
+    fn paths(&self) -> &[String] {
+        match self {
+            Bundles::Indexed(bundles) => &bundles.index.paths,
+            Bundles::Cached(cached) => cached.paths(),
+        }
+    }
+
+    fn index(&self) -> &BundleReader {
+        match self {
+            Bundles::Indexed(bundles) => bundles,
+            Bundles::Cached(cached) => cached.bundles(),
+        }
+    }
+}
+
+fn run_diff(args: &Args, (old, old_schema): (&Path, &Path), (new, new_schema): (&Path, &Path), tables: &[String]) {
+    let now = Instant::now();
+    let old_bundles = install::is_install(old).then(|| BundleReader::from_install(old));
+    let new_bundles = install::is_install(new).then(|| BundleReader::from_install(new));
+    let old_reader = version_reader(args.language(), old_bundles.as_ref(), old, old_schema);
+    let new_reader = version_reader(args.language(), new_bundles.as_ref(), new, new_schema);
+
+    let mut tables: Vec<&str> = tables.iter().map(String::as_str).collect();
+    if tables.is_empty() {
+        tables = old_reader.exports().union(&new_reader.exports()).copied().collect();
+        tables.sort();
+    }
+    let result = diff::diff(&old_reader, &new_reader, &tables);
+    info!("diff spent: {}ms", now.elapsed().as_millis());
+    write_result(args, &result);
+}
+
+fn version_reader<'a>(language: &'a str, bundles: Option<&'a BundleReader>, path: &Path, schema_path: &Path) -> DatReader<'a> {
+    match bundles {
+        Some(bundles) => DatReader::from_install(language, bundles, schema_path),
+        None if path.join("data").is_dir() => DatReader::from_directory(language, path, schema_path),
+        None => {
+            error!("{} is neither an install nor a directory with extracted data files", path.display());
+            process::exit(-1);
+        }
+    }
+}
+
+fn refuse_binary_terminal(args: &Args) {
+    if args.output_format.is_binary() && io::stdout().is_terminal() {
+        error!("Refusing to write binary output to a terminal, redirect it to a file");
+        process::exit(-1);
+    }
+}
+
+fn run_repl(args: &Args, install_path: &Path, schema_path: &Path) {
+    refuse_binary_terminal(args);
+    let bundles = Bundles::open(args, install_path);
+    let container = bundles.reader(args.language(), schema_path);
+    let context = StaticContext::new(&container).with_variables(variables(args));
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".poe_query_history"));
+
+    if let Err(error) = repl::run(&context, &container, &output_options(args), history.as_deref()) {
+        error!("{}", error);
+        process::exit(-1);
+    }
+}
+
+fn output_options(args: &Args) -> OutputOptions {
+    OutputOptions {
+        indent: match (args.compact || args.ndjson, args.tab, args.indent) {
+            (true, _, _) | (false, false, 0) => None,
+            (false, true, _) => Some(String::from("\t")),
+            (false, false, n) => Some(" ".repeat(n as usize)),
+        },
+        raw: args.raw_output || args.join_output,
+        join: args.join_output,
+        ndjson: args.ndjson,
+        format: match (&args.output_format, args.csv) {
+            (OutputFormat::Yaml, _) => Format::Encoded(Encoding::Yaml),
+            (OutputFormat::Toml, _) => Format::Encoded(Encoding::Toml),
+            (OutputFormat::Msgpack, _) => Format::Encoded(Encoding::MessagePack),
+            (OutputFormat::Cbor, _) => Format::Encoded(Encoding::Cbor),
+            (OutputFormat::Parquet, _) => Format::Columnar(Columnar::Parquet),
+            (OutputFormat::Arrow, _) => Format::Columnar(Columnar::ArrowIpc),
+            (OutputFormat::Json, true) => Format::Csv,
+            (OutputFormat::Json, false) => Format::Json,
+        },
+    }
 }
 
 fn init_logger(verbosity: u8) {
@@ -108,46 +544,170 @@ fn init_logger(verbosity: u8) {
         .unwrap_or_default();
 }
 
-fn find_poe_install(path_arg: Option<PathBuf>) -> Box<Path> {
-    match path_arg {
-        Some(path) => {
-            let is_file = path.exists() && path.is_file();
-            match is_file || contains_ggpk_or_index(&path) {
-                true => Some(path),
-                false => None
+/** JSON values from the input files or stdin, None when the query starts from the dat tables */
+fn read_inputs(args: &Args, terms: &[Term], schema_path: &Path) -> Option<Vec<Value>> {
+    let files = match args.positional_args {
+        true => vec![],
+        false => positional(args),
+    };
+    // table queries often run with an open stdin, e.g. in scripts, so it is only read unasked when there are no tables to start from
+    let implicit = files.is_empty() && !args.slurp && !args.input;
+    if args.null_input || (implicit && (io::stdin().is_terminal() || references_table(terms, None, schema_path))) {
+        return None;
+    }
+
+    let values = match files.is_empty() {
+        true => read_json(io::stdin().lock(), "stdin"),
+        false => files.iter()
+            .flat_map(|file| match file.as_os_str() == "-" {
+                true => read_json(io::stdin().lock(), "stdin"),
+                false => read_json_file(file),
+            })
+            .collect(),
+    };
+
+    match (args.slurp, values.is_empty()) {
+        (true, _) => Some(vec![Value::List(values)]),
+        // nothing was piped in, e.g. stdin is /dev/null
+        (false, true) if files.is_empty() => None,
+        (false, _) => Some(values),
+    }
+}
+
+/** Variables from --arg, --argjson, --slurpfile and --args, all of them are also in $ARGS */
+fn variables(args: &Args) -> Vec<(String, Value)> {
+    let mut variables = Vec::new();
+    for pair in args.arg.chunks(2) {
+        variables.push((pair[0].clone(), Value::Str(pair[1].clone())));
+    }
+    for pair in args.argjson.chunks(2) {
+        let value = match read_json(pair[1].as_bytes(), &format!("--argjson {}", pair[0])).as_slice() {
+            [value] => value.clone(),
+            _ => {
+                error!("--argjson {} needs exactly one JSON value", pair[0]);
+                process::exit(-1);
             }
+        };
+        variables.push((pair[0].clone(), value));
+    }
+    for pair in args.slurpfile.chunks(2) {
+        variables.push((pair[0].clone(), Value::List(read_json_file(Path::new(&pair[1])))));
+    }
+
+    let positional = match args.positional_args {
+        true => positional(args).iter().map(|arg| Value::Str(arg.display().to_string())).collect(),
+        false => vec![],
+    };
+    let named = variables.iter()
+        .map(|(name, value)| Value::KeyValue(Box::new(Value::Str(name.clone())), Box::new(value.clone())))
+        .collect();
+    let arguments = vec![
+        Value::KeyValue(Box::new(Value::Str(String::from("positional"))), Box::new(Value::List(positional))),
+        Value::KeyValue(Box::new(Value::Str(String::from("named"))), Box::new(Value::Object(Box::new(Value::List(named))))),
+    ];
+    variables.push((String::from("ARGS"), Value::Object(Box::new(Value::List(arguments)))));
+    variables
+}
+
+/** Arguments after the query, the first one is among them when the query is read from a file */
+fn positional(args: &Args) -> Vec<PathBuf> {
+    let first = args.from_file.as_ref().and(args.query.as_ref()).map(PathBuf::from);
+    first.into_iter().chain(args.files.iter().cloned()).collect()
+}
+
+fn read_json_file(file: &Path) -> Vec<Value> {
+    match File::open(file) {
+        Ok(reader) => read_json(BufReader::new(reader), &file.display().to_string()),
+        Err(error) => {
+            error!("Failed to read {}: {}", file.display(), error);
+            process::exit(-1);
         }
+    }
+}
+
+fn read_json(reader: impl Read, source: &str) -> Vec<Value> {
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter::<serde_json::Value>()
+        .map(|value| value.map(Value::from).unwrap_or_else(|error| {
+            error!("Failed to parse JSON from {}: {}", source, error);
+            process::exit(-1);
+        }))
+        .collect()
+}
+
+// only names looked up on the input can be tables, keys that every input object has are read from the input
+fn references_table(terms: &[Term], inputs: Option<&[Value]>, schema_path: &Path) -> bool {
+    let lookups: Vec<&str> = query::lookups(terms).into_iter()
+        .filter(|name| !inputs.is_some_and(|inputs| inputs.iter().all(|input| has_key(input, name))))
+        .collect();
+    if lookups.is_empty() || !schema_path.is_dir() {
+        return false;
+    }
+    let enums = FileSpec::read_enum_specs(schema_path);
+    FileSpec::read_file_specs(schema_path, &enums, &HashMap::new())
+        .values()
+        .any(|spec| lookups.contains(&spec.file_name.as_str()))
+}
+
+fn has_key(value: &Value, key: &str) -> bool {
+    let Value::Object(content) = value else { return false };
+    let entries = match content.as_ref() {
+        Value::List(entries) | Value::Iterator(entries) => entries.as_slice(),
+        content => std::slice::from_ref(content),
+    };
+    entries.iter().any(|entry| matches!(entry, Value::KeyValue(k, _) if matches!(k.as_ref(), Value::Str(k) if k == key)))
+}
+
+fn find_poe_install(path_arg: Option<PathBuf>) -> Option<Box<Path>> {
+    match path_arg {
+        Some(path) => Some(path).filter(|path| install::is_install(path)),
         None => attempt_to_find_installation()
-    }.unwrap_or_else(|| {
+    }.map(PathBuf::into_boxed_path)
+}
+
+fn require_poe_install(install_path: Option<Box<Path>>) -> Box<Path> {
+    install_path.unwrap_or_else(|| {
         error!("Path of Exile not found. Provide a valid path with -p flag.");
         process::exit(-1);
-    }).into_boxed_path()
+    })
 }
 
 fn attempt_to_find_installation() -> Option<PathBuf> {
-    [
-        ".",
-        "C:/Program Files (x86)/Grinding Gear Games/Path of Exile",
-        "C:/Program Files/Steam/steamapps/common/Path of Exile"
-    ].into_iter()
-        .find_map(|p| {
-            let path = PathBuf::from(p);
-            match contains_ggpk_or_index(&path) {
-                true => Some(path.canonicalize().unwrap()),
-                false => None
-            }
-        })
+    install::candidates(install::home().as_deref()).into_iter()
+        .find(|path| install::is_install(path))
+        .map(|path| path.canonicalize().unwrap_or(path))
 }
 
-fn contains_ggpk_or_index(path: &Path) -> bool {
-    let has_ggpk = path.join("Content.ggpk").exists();
-    let has_index = path.join("Bundles2/_.index.bin").exists();
-    has_ggpk || has_index
-}
-
-fn find_schema_path() -> Box<Path> {
+fn find_schema_path(configured: Option<PathBuf>) -> Box<Path> {
+    if let Some(schema_dir) = configured {
+        return schema_dir.into_boxed_path();
+    }
     let mut schema_dir = env::current_exe().unwrap();
     schema_dir.pop(); // remove file
     schema_dir.push("dat-schema");
     schema_dir.into_boxed_path()
-}
\ No newline at end of file
+}
+
+fn save_config(args: &Args, path: Option<PathBuf>) {
+    let Some(path) = path else {
+        error!("No config directory found, set XDG_CONFIG_HOME");
+        process::exit(-1);
+    };
+    // relative paths would only work from the current directory
+    let absolute = |path: &PathBuf| std::path::absolute(path).unwrap_or_else(|_| path.clone());
+    let given = Config {
+        install_path: args.path.as_ref().map(absolute),
+        language: args.language.clone(),
+        schema_path: args.schema.as_ref().map(absolute),
+        cache_path: args.cache_dir.as_ref().map(absolute),
+    };
+    let saved = Config::read(&path)
+        .map(|config| config.merge(given))
+        .and_then(|config| config.write(&path));
+    if let Err(error) = saved {
+        error!("{}", error);
+        process::exit(-1);
+    }
+    println!("{}", path.display());
+    print!("{}", std::fs::read_to_string(&path).unwrap_or_default());
+}
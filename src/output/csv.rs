@@ -0,0 +1,25 @@
+use std::io;
+use std::io::Write;
+
+use crate::output::table;
+use crate::traversal::format::{csv_field, json};
+use crate::traversal::value::Value;
+
+/** Writes a stream of objects as CSV, the header has every key in the order they first appear */
+pub fn write<W: Write>(out: &mut W, value: &Value) -> io::Result<()> {
+    let table = table(value)?;
+    writeln!(out, "{}", table.keys.iter().map(|key| csv_field(key)).collect::<Vec<_>>().join(","))?;
+    for row in table.rows {
+        let cells: Vec<String> = row.iter().map(|value| value.map(cell).unwrap_or_default()).collect();
+        writeln!(out, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+// nested values are stored as their compact JSON text
+fn cell(value: &Value) -> String {
+    match value {
+        Value::List(_) | Value::Iterator(_) | Value::Object(_) | Value::KeyValue(_, _) => csv_field(&Value::Str(json(value))),
+        value => csv_field(value),
+    }
+}
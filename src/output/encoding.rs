@@ -0,0 +1,57 @@
+use std::io;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::traversal::value::Value;
+
+/** Serde formats other than JSON that results can be written as */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Yaml,
+    Toml,
+    MessagePack,
+    Cbor,
+}
+
+/** Writes every item of a stream as its own document, or fails when the format can't represent the result */
+pub fn write<W: Write>(out: &mut W, value: &Value, encoding: Encoding) -> io::Result<()> {
+    let items = match value {
+        Value::Iterator(items) => items.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+
+    match encoding {
+        // documents are separated with ---
+        Encoding::Yaml => {
+            let mut serializer = serde_yaml::Serializer::new(out);
+            items.iter().try_for_each(|item| item.serialize(&mut serializer)).map_err(unrepresentable("YAML"))
+        }
+        Encoding::Toml => {
+            let item = match items {
+                [item] => item,
+                items => return Err(invalid(format!("TOML can only represent a single object, the result is a stream of {} values", items.len()))),
+            };
+            if !matches!(item, Value::Object(_)) {
+                return Err(invalid(format!("TOML can only represent an object at the top level, got {}", item)));
+            }
+            let text = toml::to_string_pretty(item).map_err(unrepresentable("TOML"))?;
+            out.write_all(text.as_bytes())
+        }
+        // a stream is written as consecutive values, which readers of both formats can iterate
+        Encoding::MessagePack => items.iter()
+            .try_for_each(|item| rmp_serde::encode::write(out, item))
+            .map_err(unrepresentable("MessagePack")),
+        Encoding::Cbor => items.iter()
+            .try_for_each(|item| ciborium::into_writer(item, &mut *out))
+            .map_err(unrepresentable("CBOR")),
+    }
+}
+
+fn unrepresentable<E: std::fmt::Display>(format: &'static str) -> impl Fn(E) -> io::Error {
+    move |error| invalid(format!("{} can't represent the result: {}", format, error))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,127 @@
+use std::io;
+use std::io::Write;
+
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+
+use crate::export::arrow::{self, Columnar};
+use crate::output::encoding::Encoding;
+use crate::traversal::value::Value;
+
+pub mod csv;
+pub mod encoding;
+
+/** How results are encoded, options other than `format` only apply to JSON */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+    Encoded(Encoding),
+    Columnar(Columnar),
+}
+
+/** Controls how query results are written */
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    /** Indentation for pretty printed JSON, compact JSON when None */
+    pub indent: Option<String>,
+    /** Strings are written without quotes */
+    pub raw: bool,
+    /** No newline is written after each value */
+    pub join: bool,
+    /** A top level list is written as one element per line */
+    pub ndjson: bool,
+    pub format: Format,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            indent: Some(String::from("  ")),
+            raw: false,
+            join: false,
+            ndjson: false,
+            format: Format::Json,
+        }
+    }
+}
+
+/** Writes a query result in the format of the options */
+pub fn write<W: Write>(out: &mut W, value: &Value, options: &OutputOptions) -> io::Result<()> {
+    match options.format {
+        Format::Json => write_json(out, value, options),
+        Format::Csv => csv::write(out, value),
+        Format::Encoded(encoding) => encoding::write(out, value, encoding),
+        Format::Columnar(columnar) => arrow::write_values(out, value, columnar),
+    }
+}
+
+/** Writes every item of a stream separately, values are serialized straight into `out` */
+fn write_json<W: Write>(out: &mut W, value: &Value, options: &OutputOptions) -> io::Result<()> {
+    match value {
+        Value::Iterator(items) => items.iter().try_for_each(|item| write_value(out, item, options)),
+        Value::List(items) if options.ndjson => items.iter().try_for_each(|item| write_value(out, item, options)),
+        value => write_value(out, value, options),
+    }
+}
+
+fn write_value<W: Write>(out: &mut W, value: &Value, options: &OutputOptions) -> io::Result<()> {
+    match (value, &options.indent) {
+        (Value::Str(text), _) if options.raw => out.write_all(text.as_bytes())?,
+        (value, None) => serde_json::to_writer(&mut *out, value)?,
+        (value, Some(indent)) => {
+            let mut serializer = Serializer::with_formatter(&mut *out, PrettyFormatter::with_indent(indent.as_bytes()));
+            value.serialize(&mut serializer)?;
+        }
+    }
+
+    if !options.join {
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/** A stream of objects as rows, for the tabular formats */
+pub struct Table<'a> {
+    /** Every key in the order they first appear */
+    pub keys: Vec<&'a Value>,
+    /** Values under each key, None when the object doesn't have it */
+    pub rows: Vec<Vec<Option<&'a Value>>>,
+}
+
+pub fn table(value: &Value) -> io::Result<Table<'_>> {
+    let rows = match value {
+        Value::Iterator(rows) | Value::List(rows) => rows.as_slice(),
+        value => std::slice::from_ref(value),
+    };
+    let rows: Vec<Vec<(&Value, &Value)>> = rows.iter().map(entries).collect::<io::Result<_>>()?;
+
+    let mut keys: Vec<&Value> = Vec::new();
+    for (key, _) in rows.iter().flatten() {
+        if !keys.contains(key) {
+            keys.push(key);
+        }
+    }
+    let rows = rows.iter()
+        .map(|row| keys.iter().map(|key| row.iter().find(|(k, _)| k == key).map(|(_, value)| *value)).collect())
+        .collect();
+    Ok(Table { keys, rows })
+}
+
+fn entries(row: &Value) -> io::Result<Vec<(&Value, &Value)>> {
+    let Value::Object(content) = row else {
+        return Err(io::Error::other(format!("Tabular output expects a stream of objects, got {}", row)));
+    };
+
+    let entries = match content.as_ref() {
+        Value::List(entries) | Value::Iterator(entries) => entries.as_slice(),
+        content => std::slice::from_ref(content),
+    };
+    Ok(entries.iter()
+        .filter_map(|entry| match entry {
+            Value::KeyValue(key, value) => Some((key.as_ref(), value.as_ref())),
+            _ => None,
+        })
+        .collect())
+}
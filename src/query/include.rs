@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+/** Replaces every `include "name";` with the query in `name.pql` relative to `directory`, wrapped in parentheses */
+pub fn expand_includes(source: &str, directory: &Path) -> Result<String, String> {
+    expand(source, directory, &mut Vec::new())
+}
+
+fn expand(source: &str, directory: &Path, including: &mut Vec<PathBuf>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = source;
+    while let Some(position) = next_include(rest) {
+        expanded.push_str(&rest[..position]);
+        let (name, remaining) = directive(&rest[position + "include".len()..])?;
+
+        let mut path = directory.join(name);
+        if path.extension().is_none() {
+            path.set_extension("pql");
+        }
+        if including.contains(&path) {
+            return Err(format!("{} includes itself", path.display()));
+        }
+        let text = std::fs::read_to_string(&path)
+            .map_err(|error| format!("Failed to include {}: {}", path.display(), error))?;
+
+        // nested includes are relative to the including file
+        including.push(path.clone());
+        let inner = expand(&text, path.parent().unwrap_or(directory), including)?;
+        including.pop();
+
+        // the newline ends a trailing comment in the included file
+        expanded.push('(');
+        expanded.push_str(&inner);
+        expanded.push_str("\n)");
+        rest = remaining;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/** Byte offset of the next `include` keyword that isn't part of a string, comment or name */
+fn next_include(source: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut comment = false;
+    let mut previous = ' ';
+    for (position, c) in source.char_indices() {
+        match (quote, comment, c) {
+            (Some(open), _, c) if c == open => quote = None,
+            (Some(_), _, _) => {}
+            (None, true, '\n') => comment = false,
+            (None, true, _) => {}
+            (None, false, '"' | '\'') => quote = Some(c),
+            (None, false, '#') => comment = true,
+            (None, false, _) => {
+                let keyword = source[position..].strip_prefix("include");
+                let separated = keyword.and_then(|rest| rest.chars().next()).is_some_and(|next| next.is_whitespace() || next == '"');
+                if separated && !is_name_char(previous) {
+                    return Some(position);
+                }
+            }
+        }
+        previous = c;
+    }
+    None
+}
+
+/** Splits ` "name"; rest` into the name and the rest of the query */
+fn directive(source: &str) -> Result<(&str, &str), String> {
+    let invalid = || format!("Expected include \"name\"; near: {}", source.lines().next().unwrap_or_default().trim());
+    let quoted = source.trim_start().strip_prefix('"').ok_or_else(invalid)?;
+    let (name, rest) = quoted.split_once('"').ok_or_else(invalid)?;
+    let rest = rest.trim_start().strip_prefix(';').ok_or_else(invalid)?;
+    Ok((name, rest))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
@@ -5,6 +5,10 @@ use log::{debug, error, trace};
 use pest::error::LineColLocation;
 use pest::Parser;
 
+mod include;
+
+pub use include::expand_includes;
+
 #[derive(Parser)]
 #[grammar = "query/grammar.pest"]
 struct PluckParser;
@@ -22,21 +26,48 @@ pub enum Term {
     ArrayConstruction(Vec<Term>),
     Select(Vec<Term>, Option<Compare>, Vec<Term>),
     Calculate(Vec<Term>, Operation, Vec<Term>),
+    Assign(Vec<Term>, Assignment, Vec<Term>),
+    Comparison(Vec<Term>, Compare, Vec<Term>),
     Iterator,
     StringLiteral(String),
     Key(Vec<Term>),
+    Group(Vec<Term>),
     SetVariable(String),
+    Destructure(Vec<Pattern>),
     GetVariable(String),
     Contains(Vec<Term>),
     UnsignedNumber(u64),
     Reduce(Vec<Term>, Vec<Term>, Vec<Term>),
+    Foreach(Vec<Term>, Vec<Term>, Vec<Term>, Vec<Term>),
+    Label(String, Vec<Term>),
+    Break(String),
     Map(Vec<Term>),
+    Add,
+    Any(Vec<Term>),
+    All(Vec<Term>),
+    Flatten(Vec<Term>),
+    Range(Vec<Vec<Term>>),
+    Min,
+    Max,
+    Indices(Vec<Term>),
+    Format(Format),
+    Limit(Vec<Term>, Vec<Term>),
+    First(Vec<Term>),
+    Last(Vec<Term>),
+    Until(Vec<Term>, Vec<Term>),
+    StatText(Vec<Term>, Vec<Term>),
+    Localized(Vec<Term>),
+    ReadFile,
+    Metadata,
+    RollableMods(Vec<Term>, Vec<Term>),
+    While(Vec<Term>, Vec<Term>),
     SignedNumber(i64),
     Transpose,
     Identity,
     CommaSeparator,
     Length,
     Keys,
+    Env,
     NoOperation,
     PipeOperator,
     _Equal,
@@ -60,6 +91,96 @@ pub enum Operation {
     Division,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Format {
+    Csv,
+    Tsv,
+    Html,
+    Sh,
+    Base64,
+    Base64Decode,
+    Uri,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Assignment {
+    Update,
+    Set,
+    Addition,
+    Subtraction,
+    Alternative,
+}
+
+/** Destructuring target of `as`, keys of an object pattern are evaluated as queries */
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pattern {
+    Variable(String),
+    Array(Vec<Pattern>),
+    Object(Vec<(Vec<Term>, Pattern)>),
+}
+
+impl Pattern {
+    /** All variable names bound by this pattern, in order of appearance */
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            Pattern::Variable(name) => vec![name.as_str()],
+            Pattern::Array(patterns) => patterns.iter().flat_map(Pattern::variables).collect(),
+            Pattern::Object(entries) => entries.iter().flat_map(|(_, pattern)| pattern.variables()).collect(),
+        }
+    }
+}
+
+/** Names looked up on the input of the query, only these can be dat tables */
+pub fn lookups(terms: &[Term]) -> Vec<&str> {
+    lookups_from(terms, true)
+}
+
+// later stages of a pipe look up fields of what the earlier ones produced, unless they follow a variable binding
+fn lookups_from(terms: &[Term], root: bool) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut root = root;
+    for stage in terms.split(|term| matches!(term, Term::PipeOperator)) {
+        for path in stage.split(|term| matches!(term, Term::CommaSeparator)) {
+            let first = path.iter().position(|term| *term != Term::Identity);
+            for (i, term) in path.iter().enumerate() {
+                let start = root && Some(i) == first;
+                match term {
+                    Term::LookupByName(name) | Term::LookupKeyValueByName(name) if start => names.push(name.as_str()),
+                    // the tables are read without being looked up
+                    Term::RollableMods(_, _) => names.extend(["BaseItemTypes", "Mods"]),
+                    _ => {}
+                }
+                names.extend(nested(term).into_iter().flat_map(|inner| lookups_from(inner, start)));
+            }
+        }
+        root = matches!(stage.last(), Some(Term::SetVariable(_) | Term::Destructure(_)));
+    }
+    names
+}
+
+/** Whether the query has a `break` anywhere in it */
+pub fn breaks(terms: &[Term]) -> bool {
+    terms.iter().any(|term| matches!(term, Term::Break(_)) || nested(term).into_iter().any(breaks))
+}
+
+/** Queries inside a term, like the arguments of a builtin */
+fn nested(term: &Term) -> Vec<&[Term]> {
+    match term {
+        Term::KeyValue(key, value) => vec![std::slice::from_ref(key.as_ref()), value],
+        Term::Select(lhs, _, rhs) | Term::Calculate(lhs, _, rhs) | Term::Assign(lhs, _, rhs) |
+        Term::Comparison(lhs, _, rhs) | Term::Limit(lhs, rhs) | Term::Until(lhs, rhs) | Term::While(lhs, rhs) |
+        Term::StatText(lhs, rhs) | Term::RollableMods(lhs, rhs) => vec![lhs, rhs],
+        Term::Reduce(source, init, update) => vec![source, init, update],
+        Term::Foreach(source, init, update, extract) => vec![source, init, update, extract],
+        Term::ObjectConstruction(inner) | Term::ArrayConstruction(inner) | Term::Key(inner) | Term::Group(inner) |
+        Term::Contains(inner) | Term::Label(_, inner) | Term::Map(inner) | Term::Any(inner) | Term::All(inner) |
+        Term::Flatten(inner) | Term::Indices(inner) | Term::First(inner) | Term::Last(inner) | Term::Localized(inner) => vec![inner],
+        Term::Range(arguments) => arguments.iter().map(Vec::as_slice).collect(),
+        _ => vec![],
+    }
+}
+
 pub fn parse_query(source: &str) -> Result<Vec<Term>, String> {
     let pairs = match PluckParser::parse(Rule::program, source) {
         Ok(pairs) => pairs,
@@ -87,45 +208,98 @@ fn build_ast(pair: pest::iterators::Pair<Rule>) -> Vec<Term> {
 
     match pair.as_rule() {
         Rule::multiple_terms => {
-            pair.into_inner().into_iter()
+            localize_pipe(pair.into_inner()
                 .flat_map(build_ast)
-                .collect::<Vec<_>>()
+                .collect::<Vec<_>>())
+        }
+        Rule::additive | Rule::multiplicative | Rule::comparison => {
+            // operands are terms spanning several pairs, the operators between them fold from the left
+            let mut operands = vec![Vec::new()];
+            let mut operators = Vec::new();
+            for next in pair.into_inner() {
+                match next.as_rule() {
+                    Rule::additive_operation | Rule::multiplicative_operation | Rule::compare => {
+                        operators.push(next);
+                        operands.push(Vec::new());
+                    }
+                    _ => operands.last_mut().unwrap().append(&mut build_ast(next)),
+                }
+            }
+            let mut operands = operands.into_iter();
+            let first = operands.next().unwrap();
+            operators.into_iter().zip(operands).fold(first, |lhs, (operator, rhs)| match operator.as_rule() {
+                Rule::compare => vec![Term::Comparison(lhs, to_compare(operator), rhs)],
+                _ => vec![Term::Calculate(lhs, to_operation(operator), rhs)],
+            })
         }
-        Rule::calculation => {
-            let mut left_operand = Vec::new();
-            let mut right_operand = Vec::new();
-            let mut current = &mut left_operand;
-            let mut operation = None;
+        Rule::assignment => {
+            let mut path = Vec::new();
+            let mut value = Vec::new();
+            let mut current = &mut path;
+            let mut assignment = None;
             for next in pair.into_inner() {
                 match next.as_rule() {
-                    Rule::operation => {
-                        operation = match next.into_inner().next().unwrap().as_rule() {
-                            Rule::add => Some(Operation::Addition),
-                            Rule::subtract => Some(Operation::Subtraction),
-                            Rule::multiply => Some(Operation::Multiplication),
-                            Rule::divide => Some(Operation::Division),
+                    Rule::assign_operation => {
+                        assignment = match next.into_inner().next().unwrap().as_rule() {
+                            Rule::update_assign => Some(Assignment::Update),
+                            Rule::set_assign => Some(Assignment::Set),
+                            Rule::add_assign => Some(Assignment::Addition),
+                            Rule::subtract_assign => Some(Assignment::Subtraction),
+                            Rule::alternative_assign => Some(Assignment::Alternative),
                             rule => {
-                                error!("Unexpected rule '{:?}'. Expected math operation.", rule);
+                                error!("Unexpected rule '{:?}'. Expected assignment operation.", rule);
                                 process::exit(-1);
                             }
                         };
-                        current = &mut right_operand;
+                        current = &mut value;
                     }
                     _ => current.append(&mut build_ast(next)),
                 }
             }
 
-            match (operation, left_operand, right_operand) {
-                (None, lhs, _) => lhs,
-                (Some(op), lhs, rhs) =>
-                    vec![Term::Calculate(lhs, op, rhs)]
+            match assignment {
+                None => path,
+                Some(assignment) => vec![Term::Assign(path, assignment, value)],
             }
         }
         Rule::zip_to_obj => zip_to_object_terms(),
+        Rule::describe_mod => describe_mod_terms(),
+        // without arguments these are plain lookups
+        Rule::first if pair.clone().into_inner().next().is_none() => vec![Term::LookupByIndex(0)],
+        Rule::last if pair.clone().into_inner().next().is_none() => vec![Term::ByIndexReverse(1)],
         _ => vec![to_term(pair)]
     }
 }
 
+// `a | localized | b` is `localized(a) | b`, the tables read before it have to be read again in each language
+fn localize_pipe(terms: Vec<Term>) -> Vec<Term> {
+    if !terms.contains(&Term::Localized(vec![])) {
+        return terms;
+    }
+    let mut result = Vec::new();
+    for (i, segment) in terms.split(|term| *term == Term::PipeOperator).enumerate() {
+        if segment == [Term::Localized(vec![])] {
+            result = vec![Term::Localized(result)];
+            continue;
+        }
+        if i > 0 {
+            result.push(Term::PipeOperator);
+        }
+        result.extend_from_slice(segment);
+    }
+    result
+}
+
+// the stats of a Mods row with their ranges
+fn describe_mod_terms() -> Vec<Term> {
+    let stats = (1..=6).map(|i| format!(".StatsKey{}.Id", i)).collect::<Vec<_>>().join(", ");
+    let values = (1..=6).map(|i| format!("[.Stat{0}Min, .Stat{0}Max]", i)).collect::<Vec<_>>().join(", ");
+    parse_query(&format!("stat_text([{}]; [{}])", stats, values)).expect("describe_mod is valid")
+        .into_iter()
+        .filter(|term| *term != Term::NoOperation)
+        .collect()
+}
+
 fn zip_to_object_terms() -> Vec<Term> {
     vec![
         Term::Transpose,
@@ -134,24 +308,79 @@ fn zip_to_object_terms() -> Vec<Term> {
                 Term::KeyValue(Box::new(Term::Key(vec![Term::LookupByIndex(0)])), vec![Term::LookupByIndex(1)])
             ])
         ]),
-        Term::Reduce(
-            vec![
-                Term::Identity,
-                Term::Iterator,
-                Term::SetVariable("item".to_string()),
-            ],
-            vec![Term::ObjectConstruction(vec![])],
-            vec![
-                Term::Calculate(
-                    vec![Term::Identity],
-                    Operation::Addition,
-                    vec![Term::GetVariable("item".to_string())],
-                )
-            ],
-        ),
+        Term::Add,
     ]
 }
 
+fn to_operation(pair: pest::iterators::Pair<Rule>) -> Operation {
+    match pair.into_inner().next().unwrap().as_rule() {
+        Rule::add => Operation::Addition,
+        Rule::subtract => Operation::Subtraction,
+        Rule::multiply => Operation::Multiplication,
+        Rule::divide => Operation::Division,
+        rule => {
+            error!("Unexpected rule '{:?}'. Expected math operation.", rule);
+            process::exit(-1);
+        }
+    }
+}
+
+fn to_compare(pair: pest::iterators::Pair<Rule>) -> Compare {
+    match pair.into_inner().next().unwrap().as_rule() {
+        Rule::equal => Compare::Equals,
+        Rule::not_equal => Compare::NotEquals,
+        Rule::less_than => Compare::LessThan,
+        Rule::greater_than => Compare::GreaterThan,
+        Rule::less_than_eq => Compare::LessThanEq,
+        Rule::greater_than_eq => Compare::GreaterThanEq,
+        rule => {
+            error!("Unexpected rule '{:?}'. Expected comparison operation.", rule);
+            process::exit(-1);
+        }
+    }
+}
+
+fn to_pattern(pair: pest::iterators::Pair<Rule>) -> Pattern {
+    match pair.as_rule() {
+        Rule::variable => Pattern::Variable(pair.into_inner().as_str().to_string()),
+        Rule::array_pattern => Pattern::Array(pair.into_inner().map(to_pattern).collect()),
+        Rule::object_pattern => Pattern::Object(pair.into_inner().flat_map(|entry| {
+            let mut inner = entry.into_inner();
+            let key = inner.next().unwrap();
+            let pattern = inner.next().map(to_pattern);
+            match (key.as_rule(), pattern) {
+                // {$name} and {$name: pattern} bind .name to $name as well
+                (Rule::variable, pattern) => {
+                    let name = key.into_inner().as_str().to_string();
+                    let key = vec![Term::StringLiteral(name.to_string())];
+                    let mut entries = vec![(key.clone(), Pattern::Variable(name))];
+                    entries.extend(pattern.map(|pattern| (key, pattern)));
+                    entries
+                }
+                (Rule::group, Some(pattern)) => vec![(vec![to_term(key)], pattern)],
+                (_, Some(pattern)) => vec![(vec![Term::StringLiteral(key.as_str().to_string())], pattern)],
+                (_, None) => {
+                    error!("Object pattern key '{}' must be bound to a variable or pattern", key.as_str());
+                    process::exit(-1);
+                }
+            }
+        }).collect()),
+        rule => {
+            error!("Unexpected rule '{:?}'. Expected destructuring pattern.", rule);
+            process::exit(-1);
+        }
+    }
+}
+
+fn arguments(pair: pest::iterators::Pair<Rule>) -> Vec<Vec<Term>> {
+    pair.into_inner()
+        .filter(|argument| argument.as_rule() == Rule::argument)
+        .map(|argument| argument.into_inner()
+            .flat_map(build_ast)
+            .collect())
+        .collect()
+}
+
 fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
     trace!("{:?}", pair.as_rule());
     match pair.as_rule() {
@@ -162,16 +391,45 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
         Rule::comma => Term::CommaSeparator,
         Rule::length => Term::Length,
         Rule::keys => Term::Keys,
+        Rule::env => Term::Env,
+        Rule::read_file => Term::ReadFile,
+        Rule::metadata => Term::Metadata,
         Rule::transpose => Term::Transpose,
+        Rule::format => Term::Format(match pair.into_inner().next().unwrap().as_rule() {
+            Rule::csv => Format::Csv,
+            Rule::tsv => Format::Tsv,
+            Rule::html => Format::Html,
+            Rule::sh => Format::Sh,
+            Rule::base64 => Format::Base64,
+            Rule::base64d => Format::Base64Decode,
+            Rule::uri => Format::Uri,
+            Rule::json => Format::Json,
+            rule => {
+                error!("Unexpected rule '{:?}'. Expected format string.", rule);
+                process::exit(-1);
+            }
+        }),
+        Rule::bool_constant => match pair.into_inner().next().unwrap().as_rule() {
+            Rule::TRUE => Term::BoolLiteral(true),
+            _ => Term::BoolLiteral(false),
+        },
+        Rule::add_values => Term::Add,
+        Rule::min => Term::Min,
+        Rule::max => Term::Max,
         Rule::field => Term::LookupByName(pair.as_span().as_str().to_string()),
         Rule::kv_by_field => Term::LookupKeyValueByName(pair.as_span().as_str().to_string()),
-        Rule::string => Term::StringLiteral(pair.as_span().as_str().to_string()),
+        Rule::string => {
+            let quoted = pair.as_span().as_str();
+            Term::StringLiteral(quoted[1..quoted.len() - 1].to_string())
+        }
         Rule::identifier => Term::StringLiteral(pair.as_span().as_str().to_string()),
 
         Rule::assign_variable => {
-            let mut inner = pair.into_inner();
-            let text = inner.next().unwrap().into_inner().as_str();
-            Term::SetVariable(text.to_string())
+            let patterns: Vec<Pattern> = pair.into_inner().map(to_pattern).collect();
+            match patterns.as_slice() {
+                [Pattern::Variable(name)] => Term::SetVariable(name.to_string()),
+                _ => Term::Destructure(patterns),
+            }
         }
         Rule::variable => {
             let mut inner = pair.into_inner();
@@ -194,6 +452,12 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
                 Term::LookupByIndex(index as usize)
             }
         }
+        Rule::group => {
+            let terms = pair.into_inner()
+                .flat_map(build_ast)
+                .collect::<Vec<_>>();
+            Term::Group(terms)
+        }
         Rule::map => {
             let inner = pair.into_inner();
             let terms = inner.into_iter()
@@ -201,6 +465,40 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
                 .collect::<Vec<_>>();
             Term::Map(terms)
         }
+        Rule::any | Rule::all | Rule::flatten | Rule::indices | Rule::first | Rule::last => {
+            let rule = pair.as_rule();
+            let argument = arguments(pair).into_iter().next().unwrap_or_default();
+            match rule {
+                Rule::any if argument.is_empty() => Term::Any(vec![Term::Identity]),
+                Rule::all if argument.is_empty() => Term::All(vec![Term::Identity]),
+                Rule::any => Term::Any(argument),
+                Rule::all => Term::All(argument),
+                Rule::flatten => Term::Flatten(argument),
+                Rule::indices => Term::Indices(argument),
+                Rule::first => Term::First(argument),
+                _ => Term::Last(argument),
+            }
+        }
+        Rule::localized => Term::Localized(arguments(pair).into_iter().next().unwrap_or_default()),
+        Rule::range => Term::Range(arguments(pair)),
+        Rule::rollable_mods => {
+            let mut arguments = arguments(pair).into_iter();
+            Term::RollableMods(arguments.next().unwrap_or_default(), arguments.next().unwrap_or_default())
+        }
+        Rule::limit | Rule::until_loop | Rule::while_loop | Rule::stat_text => {
+            let rule = pair.as_rule();
+            let mut arguments = arguments(pair).into_iter();
+            let (Some(first), Some(second)) = (arguments.next(), arguments.next()) else {
+                error!("Parsing failed Rule::{:?}. This is a bug in the language spec.", rule);
+                process::exit(-1);
+            };
+            match rule {
+                Rule::limit => Term::Limit(first, second),
+                Rule::until_loop => Term::Until(first, second),
+                Rule::stat_text => Term::StatText(first, second),
+                _ => Term::While(first, second),
+            }
+        }
         Rule::signed_number => {
             let mut inner = pair.into_inner();
             let Some(next) = inner.next() else {
@@ -241,18 +539,7 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
                         return Term::Select(vec![bool], None, vec![]);
                     }
                     Rule::compare => {
-                        comparison = match next.into_inner().next().unwrap().as_rule() {
-                            Rule::equal => Some(Compare::Equals),
-                            Rule::not_equal => Some(Compare::NotEquals),
-                            Rule::less_than => Some(Compare::LessThan),
-                            Rule::greater_than => Some(Compare::GreaterThan),
-                            Rule::less_than_eq => Some(Compare::LessThanEq),
-                            Rule::greater_than_eq => Some(Compare::GreaterThanEq),
-                            rule => {
-                                error!("Unexpected rule '{:?}'. Expected comparison operation.", rule);
-                                process::exit(-1);
-                            }
-                        };
+                        comparison = Some(to_compare(next));
                         current = &mut rhs;
                     }
                     _ => current.push(to_term(next)),
@@ -290,11 +577,9 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
             Term::SliceData(from, to)
         }
         Rule::array_construction => {
-            let content = pair.into_inner();
-            let mut items = Vec::new();
-            for next in content {
-                items.push(to_term(next));
-            }
+            let items = pair.into_inner()
+                .flat_map(build_ast)
+                .collect();
             Term::ArrayConstruction(items)
         }
         Rule::object_construct => {
@@ -320,6 +605,33 @@ fn to_term(pair: pest::iterators::Pair<Rule>) -> Term {
             }
             Term::ObjectConstruction(object_terms)
         }
+        Rule::foreach => {
+            let mut source = Vec::new();
+            let mut arguments = Vec::new();
+            for next in pair.into_inner() {
+                match next.as_rule() {
+                    Rule::argument => arguments.push(next.into_inner().flat_map(build_ast).collect::<Vec<_>>()),
+                    _ => source.append(&mut build_ast(next)),
+                }
+            }
+
+            let mut arguments = arguments.into_iter();
+            let (Some(init), Some(update)) = (arguments.next(), arguments.next()) else {
+                error!("Parsing failed Rule::foreach. This is a bug in the language spec.");
+                process::exit(-1);
+            };
+            Term::Foreach(source, init, update, arguments.next().unwrap_or_default())
+        }
+        Rule::label => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().unwrap().into_inner().as_str().to_string();
+            let terms = inner.flat_map(build_ast).collect();
+            Term::Label(name, terms)
+        }
+        Rule::break_label => {
+            let name = pair.into_inner().next().unwrap().into_inner().as_str().to_string();
+            Term::Break(name)
+        }
         Rule::reduce => {
             let inner = pair.into_inner();
 
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+use log::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+
+use crate::dat::DatStoreImpl;
+use crate::dat::specification::FieldSpec;
+use crate::output;
+use crate::output::OutputOptions;
+use crate::query;
+use crate::traversal::{QueryProcessor, SharedCache, StaticContext};
+
+const COMMANDS: [&str; 4] = [":help", ":tables", ":schema", ":quit"];
+
+/** Reads queries until :quit or end of input, dat files stay decoded between queries */
+pub fn run<'a>(context: &StaticContext, store: &impl DatStoreImpl<'a>, options: &OutputOptions, history: Option<&Path>) -> rustyline::Result<()> {
+    let mut editor: Editor<QueryHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(QueryHelper::new(store)));
+    if let Some(history) = history {
+        // there is no history the first time
+        let _ = editor.load_history(history);
+    }
+
+    let mut cache = SharedCache::default();
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        let tables = &editor.helper().unwrap().tables;
+        match line.split_once(' ').unwrap_or((line, "")) {
+            (":quit" | ":q", _) => break,
+            (":help", _) => println!("{}", HELP),
+            (":tables", _) => {
+                let mut names: Vec<&String> = tables.keys().collect();
+                names.sort();
+                names.iter().for_each(|name| println!("{}", name));
+            }
+            (":schema", table) => match tables.get(table.trim()) {
+                Some(fields) => fields.iter().for_each(|field| match &field.file_name {
+                    Some(target) => println!("{} -> {}", field, target),
+                    None => println!("{}", field),
+                }),
+                None => error!("No table named '{}', see :tables", table.trim()),
+            },
+            (command, _) if command.starts_with(':') => error!("Unknown command {}, see :help", command),
+            _ => evaluate(context, &mut cache, line, options),
+        }
+    }
+
+    if let Some(history) = history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+const HELP: &str = "Queries are run against the dat tables, tab completes table and field names.
+  :tables          list all tables
+  :schema <table>  list the fields of a table, foreign keys show the table they refer to
+  :quit            exit, as does ctrl-d";
+
+fn evaluate(context: &StaticContext, cache: &mut SharedCache, line: &str, options: &OutputOptions) {
+    let now = Instant::now();
+    let terms = match query::expand_includes(line, Path::new(".")).and_then(|line| query::parse_query(&line)) {
+        Ok(terms) => terms,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    let result = match context.process_cached(&terms, cache) {
+        Ok(result) => result,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    let mut out = io::stdout().lock();
+    if let Err(error) = output::write(&mut out, &result, options).and_then(|_| out.flush()) {
+        error!("Failed to write output: {}", error);
+    }
+    info!("query: {}ms", now.elapsed().as_millis());
+}
+
+/** Completes table names, the fields of the table a path leads to and meta commands */
+#[derive(Helper, Highlighter, Hinter, Validator)]
+pub struct QueryHelper {
+    tables: HashMap<String, Vec<FieldSpec>>,
+}
+
+impl Completer for QueryHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        if let Some(table) = before.strip_prefix(":schema ") {
+            return Ok((pos - table.len(), matching(self.tables.keys().map(String::as_str), table)));
+        }
+        if before.starts_with(':') {
+            return Ok((0, matching(COMMANDS.into_iter(), before)));
+        }
+
+        let start = before.char_indices().rev()
+            .take_while(|(_, c)| is_name_char(*c))
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let candidates = match before[..start].ends_with('.') {
+            true => self.names_after(&before[..start]),
+            false => vec![],
+        };
+        Ok((start, matching(candidates.into_iter(), &before[start..])))
+    }
+}
+
+impl QueryHelper {
+    pub fn new<'a>(store: &impl DatStoreImpl<'a>) -> Self {
+        QueryHelper {
+            tables: store.exports().into_iter()
+                .filter_map(|export| store.spec_by_export(export))
+                .map(|spec| (spec.file_name.clone(), spec.file_fields.clone()))
+                .collect(),
+        }
+    }
+
+    // tables at the start of a path, fields of the table the path leads to otherwise
+    fn names_after(&self, text: &str) -> Vec<&str> {
+        let paths: Vec<Vec<&str>> = text
+            .split(|c: char| !(is_name_char(c) || matches!(c, '.' | '[' | ']' | ':' | '-')))
+            .filter(|path| path.starts_with('.'))
+            .map(segments)
+            .collect();
+        let Some((current, previous)) = paths.split_last() else {
+            return self.tables.keys().map(String::as_str).collect();
+        };
+
+        // earlier paths give the table when the current one doesn't start with one, like `.Mods[] | select(.`
+        let mut table = None;
+        for path in previous {
+            table = self.resolve(path, table).or(table);
+        }
+        let fields = self.resolve(current, table)
+            .and_then(|table| self.tables.get(table))
+            .map(|fields| fields.iter().map(|field| field.field_name.as_str()).collect())
+            .unwrap_or_default();
+        match current.is_empty() {
+            true => [fields, self.tables.keys().map(String::as_str).collect()].concat(),
+            false => fields,
+        }
+    }
+
+    /** Table that a path of fields ends up in, following foreign keys */
+    fn resolve<'s>(&'s self, path: &[&str], start: Option<&'s str>) -> Option<&'s str> {
+        let (mut table, fields) = match path.split_first() {
+            Some((first, rest)) if self.tables.contains_key(*first) => (self.tables.get_key_value(*first)?.0.as_str(), rest),
+            _ => (start?, path),
+        };
+        for name in fields {
+            let field = self.tables.get(table)?.iter().find(|field| field.field_name == *name)?;
+            table = field.file_name.as_deref()?;
+        }
+        Some(table)
+    }
+}
+
+fn matching<'s>(names: impl Iterator<Item = &'s str>, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = names.filter(|name| name.starts_with(prefix)).map(str::to_string).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// `.Mods[0].ModTypeKey.` is Mods, ModTypeKey
+fn segments(path: &str) -> Vec<&str> {
+    path.split('.')
+        .map(|segment| segment.split('[').next().unwrap_or_default())
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,121 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::query::Format;
+use crate::traversal::utils::fail;
+use crate::traversal::value::Value;
+
+/** Applies a jq `@format` string, the result is always a string */
+pub fn format(value: Value, format: &Format) -> Value {
+    let text = match format {
+        Format::Csv => row(value, "csv").iter().map(csv_field).collect::<Vec<_>>().join(","),
+        Format::Tsv => row(value, "tsv").iter().map(tsv_field).collect::<Vec<_>>().join("\t"),
+        Format::Html => escape_html(&text(value)),
+        Format::Sh => match value {
+            Value::List(values) | Value::Iterator(values) => values.iter().map(shell_word).collect::<Vec<_>>().join(" "),
+            value => shell_word(&value),
+        },
+        Format::Base64 => STANDARD.encode(bytes(value)),
+        Format::Base64Decode => match STANDARD.decode(text(value)) {
+            Ok(decoded) => String::from_utf8_lossy(&decoded).to_string(),
+            Err(error) => {
+                fail(format!("Failed to decode base64: {}", error));
+            }
+        },
+        Format::Uri => escape_uri(&text(value)),
+        Format::Json => json(&value),
+    };
+    Value::Str(text)
+}
+
+/** A single CSV cell, strings are always quoted */
+pub fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Str(text) => format!("\"{}\"", text.replace('"', "\"\"")),
+        value => scalar(value, "csv"),
+    }
+}
+
+/** Compact JSON text of a value */
+pub fn json(value: &Value) -> String {
+    serde_json::to_string(value).expect("values are always serializable")
+}
+
+fn tsv_field(value: &Value) -> String {
+    match value {
+        Value::Str(text) => text
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        value => scalar(value, "tsv"),
+    }
+}
+
+fn shell_word(value: &Value) -> String {
+    match value {
+        Value::Str(text) => format!("'{}'", text.replace('\'', "'\\''")),
+        value => scalar(value, "sh"),
+    }
+}
+
+fn scalar(value: &Value, format: &str) -> String {
+    match value {
+        Value::Empty => String::new(),
+        Value::Byte(_) | Value::U64(_) | Value::I64(_) | Value::F32(_) | Value::F64(_) | Value::Bool(_) => json(value),
+        unexpected => {
+            fail(format!("Type {} is not valid in a {} row", unexpected, format));
+        }
+    }
+}
+
+fn row(value: Value, format: &str) -> Vec<Value> {
+    match value {
+        Value::List(values) | Value::Iterator(values) => values,
+        unexpected => {
+            fail(format!("Type {} cannot be {}-formatted, only a list can be", unexpected, format));
+        }
+    }
+}
+
+fn text(value: Value) -> String {
+    match value {
+        Value::Str(text) => text,
+        value => json(&value),
+    }
+}
+
+// lists of bytes, e.g. a binary file from read_file, are encoded as is instead of as JSON text
+fn bytes(value: Value) -> Vec<u8> {
+    match value {
+        Value::List(values) if !values.is_empty() && values.iter().all(|value| matches!(value, Value::Byte(_))) => values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Byte(byte) => Some(byte),
+                _ => None,
+            })
+            .collect(),
+        value => text(value).into_bytes(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '\'' => escaped.push_str("&#39;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+fn escape_uri(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+        byte => format!("%{:02X}", byte),
+    }).collect()
+}
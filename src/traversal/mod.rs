@@ -3,23 +3,39 @@ use crate::{DatReader, Term};
 pub mod value;
 mod traverse;
 mod utils;
+mod path;
+pub(crate) mod format;
+
+pub use traverse::SharedCache;
 
 // TODO:
 //  Consider splitting QueryProcessor trait into multiple traits that each define a specific behavior or capability, such as DataAccessor, DataTransformer, or DataAggregator.
 pub trait QueryProcessor {
     fn process(&self, terms: &[Term]) -> value::Value;
+    /** Runs the query with `input` as the identity instead of the dat tables */
+    fn process_input(&self, terms: &[Term], input: value::Value) -> value::Value;
+    /** Like process, but dat files decoded by earlier queries are reused from `cache` and errors are returned */
+    fn process_cached(&self, terms: &[Term], cache: &mut SharedCache) -> Result<value::Value, String>;
 }
 
 /** Immutable data during traversal */
 #[derive(Default)]
 pub struct StaticContext<'a> {
     store: Option<&'a DatReader<'a>>,
+    variables: Vec<(String, value::Value)>,
 }
 
 impl<'a> StaticContext<'a> {
     pub fn new(reader: &'a DatReader<'a>) -> Self {
         StaticContext {
-            store: Some(reader)
+            store: Some(reader),
+            variables: Vec::new(),
         }
     }
+
+    /** Variables that are defined before the query runs, e.g. from --arg */
+    pub fn with_variables(mut self, variables: Vec<(String, value::Value)>) -> Self {
+        self.variables = variables;
+        self
+    }
 }
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+use crate::Term;
+use crate::traversal::utils::fail;
+use crate::traversal::value::Value;
+
+/** A single step into a value, either an object key or a list index */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathElement {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for PathElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PathElement::Key(key) => write!(f, ".{}", key),
+            PathElement::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/** Resolves the left hand side of an assignment into the concrete paths it refers to in `value`, `selected` tells if a select keeps the value at a path */
+pub fn paths(value: &Value, terms: &[Term], selected: &mut dyn FnMut(&Term, Value) -> bool) -> Vec<Vec<PathElement>> {
+    let mut resolved = vec![Vec::new()];
+    for term in terms {
+        resolved = match term {
+            Term::Identity | Term::PipeOperator | Term::NoOperation => resolved,
+            Term::Group(terms) => resolved.into_iter()
+                .flat_map(|path| paths(&get(value, &path), terms, selected).into_iter()
+                    .map(move |inner| [path.clone(), inner].concat())
+                    .collect::<Vec<_>>())
+                .collect(),
+            Term::Select(..) => resolved.into_iter()
+                .filter(|path| selected(term, get(value, path)))
+                .collect(),
+            Term::LookupByName(key) => resolved.into_iter()
+                .map(|path| append(path, PathElement::Key(key.to_string())))
+                .collect(),
+            Term::LookupByIndex(index) => resolved.into_iter()
+                .map(|path| append(path, PathElement::Index(*index)))
+                .collect(),
+            Term::ByIndexReverse(index) => resolved.into_iter()
+                .map(|path| {
+                    let length = match get(value, &path) {
+                        Value::List(list) | Value::Iterator(list) => list.len(),
+                        _ => 0,
+                    };
+                    append(path, PathElement::Index(length.saturating_sub(*index)))
+                })
+                .collect(),
+            Term::Iterator => resolved.into_iter()
+                .flat_map(|path| {
+                    let elements: Vec<PathElement> = match get(value, &path) {
+                        Value::List(list) | Value::Iterator(list) => (0..list.len()).map(PathElement::Index).collect(),
+                        Value::Object(content) => entries_ref(&content).iter()
+                            .filter_map(|entry| match entry {
+                                Value::KeyValue(key, _) => match key.as_ref() {
+                                    Value::Str(key) => Some(PathElement::Key(key.to_string())),
+                                    _ => None,
+                                },
+                                _ => None,
+                            })
+                            .collect(),
+                        Value::Empty => vec![],
+                        unexpected => {
+                            fail(format!("Type {unexpected} cannot be iterated over"));
+                        }
+                    };
+                    elements.into_iter().map(move |element| append(path.clone(), element))
+                })
+                .collect(),
+            unexpected => {
+                fail(format!("Invalid path expression in assignment: {:?}", unexpected));
+            }
+        };
+    }
+    resolved
+}
+
+/** Reads the value at `path`, missing keys and indices are Empty */
+pub fn get(value: &Value, path: &[PathElement]) -> Value {
+    let Some((first, rest)) = path.split_first() else {
+        return value.clone();
+    };
+
+    match (first, value) {
+        (PathElement::Key(key), Value::Object(content)) => {
+            let key = Value::Str(key.to_string());
+            let found = entries_ref(content).iter().find_map(|entry| match entry {
+                Value::KeyValue(k, v) if **k == key => Some(v.as_ref()),
+                _ => None,
+            });
+            get(found.unwrap_or(&Value::Empty), rest)
+        }
+        (PathElement::Index(index), Value::List(list) | Value::Iterator(list)) => {
+            get(list.get(*index).unwrap_or(&Value::Empty), rest)
+        }
+        (_, Value::Empty) => Value::Empty,
+        (element, unexpected) => {
+            fail(format!("Cannot index {} with '{}'", unexpected, element));
+        }
+    }
+}
+
+/** Returns a copy of `value` where the value at `path` has been replaced */
+pub fn set(value: Value, path: &[PathElement], new_value: Value) -> Value {
+    let Some((first, rest)) = path.split_first() else {
+        return new_value;
+    };
+
+    match (first, value) {
+        (PathElement::Key(key), Value::Object(content)) => {
+            let key = Value::Str(key.to_string());
+            let mut entries = entries(*content);
+            let position = entries.iter().position(|entry| matches!(entry, Value::KeyValue(k, _) if **k == key));
+            match position {
+                Some(i) => {
+                    let Value::KeyValue(k, v) = std::mem::replace(&mut entries[i], Value::Empty) else {
+                        unreachable!("position only matches key values");
+                    };
+                    entries[i] = Value::KeyValue(k, Box::new(set(*v, rest, new_value)));
+                }
+                None => entries.push(Value::KeyValue(Box::new(key), Box::new(set(Value::Empty, rest, new_value)))),
+            }
+            Value::Object(Box::new(Value::List(entries)))
+        }
+        (PathElement::Index(index), Value::List(mut list) | Value::Iterator(mut list)) => {
+            if list.len() <= *index {
+                list.resize(index + 1, Value::Empty);
+            }
+            let current = std::mem::replace(&mut list[*index], Value::Empty);
+            list[*index] = set(current, rest, new_value);
+            Value::List(list)
+        }
+        (PathElement::Key(_), Value::Empty) => {
+            set(Value::Object(Box::new(Value::List(vec![]))), path, new_value)
+        }
+        (PathElement::Index(_), Value::Empty) => {
+            set(Value::List(vec![]), path, new_value)
+        }
+        (element, unexpected) => {
+            fail(format!("Cannot index {} with '{}'", unexpected, element));
+        }
+    }
+}
+
+fn append(mut path: Vec<PathElement>, element: PathElement) -> Vec<PathElement> {
+    path.push(element);
+    path
+}
+
+fn entries_ref(content: &Value) -> &[Value] {
+    match content {
+        Value::List(entries) | Value::Iterator(entries) => entries,
+        Value::KeyValue(_, _) => std::slice::from_ref(content),
+        _ => &[],
+    }
+}
+
+fn entries(content: Value) -> Vec<Value> {
+    match content {
+        Value::List(entries) | Value::Iterator(entries) => entries,
+        Value::KeyValue(_, _) => vec![content],
+        Value::Empty => vec![],
+        unexpected => {
+            fail(format!("object contained an unexpected value: {:?}", unexpected));
+        }
+    }
+}
@@ -1,31 +1,164 @@
-use std::cmp::min;
-use std::collections::HashMap;
-use std::process;
+use std::cmp::{min, Ordering};
+use std::sync::Arc;
 
 use log::*;
 
 use crate::{Term};
 use crate::dat::file::DatFile;
 use crate::dat::DatStoreImpl;
-use crate::dat::specification::{FieldSpecImpl, FileSpec};
-use crate::query::{Compare, Operation};
+use crate::dat::specification::{FieldSpecImpl, FileSpec, FileSpecImpl};
+use crate::dat::object_template::ObjectTemplates;
+use crate::dat::stat_description::{StatDescriptions, STAT_DESCRIPTIONS};
+use crate::query;
+use crate::query::{Assignment, Compare, Operation, Pattern};
 use crate::traversal::{StaticContext, QueryProcessor};
-use crate::traversal::utils::{iterate, reduce};
+use crate::traversal::format;
+use crate::traversal::path;
+use crate::traversal::path::PathElement;
+use crate::traversal::utils::{catch_errors, elements, environment, fail, file_contents, flatten, iterate};
 
 use super::value::Value;
 
 /** entry point */
 impl QueryProcessor for StaticContext<'_> {
     fn process(&self, terms: &[Term]) -> Value {
-        self.traverse(&mut TraversalContext::default(), &mut SharedCache::default(), terms)
+        self.traverse(&mut self.root_context(None), &mut SharedCache::default(), terms)
+    }
+
+    fn process_input(&self, terms: &[Term], input: Value) -> Value {
+        self.traverse(&mut self.root_context(Some(input)), &mut SharedCache::default(), terms)
+    }
+
+    fn process_cached(&self, terms: &[Term], cache: &mut SharedCache) -> Result<Value, String> {
+        // only the parsed files carry over from the previous query, tables are cached by the store
+        cache.labels.clear();
+        cache.breaking = None;
+        cache.language = None;
+        catch_errors(|| self.traverse(&mut self.root_context(None), cache, terms))
+    }
+}
+
+impl StaticContext<'_> {
+    fn file(&self, name: &str) -> DatFile {
+        self.store.unwrap().file_by_filename(name).unwrap()
+    }
+
+    /** Rows of a table as objects, the `@localized` fields are read in the language `localized` is reading */
+    fn rows(&self, cache: &SharedCache, spec: &FileSpec, indices: impl Iterator<Item = u64>) -> Vec<Value> {
+        let store = self.store.unwrap();
+        let file = self.file(&spec.file_name);
+        let localized = match (&cache.language, spec.file_fields.iter().any(|field| field.localized)) {
+            (Some(language), true) => store.file_in_language(&spec.file_name, language).unwrap(),
+            _ => file.clone(),
+        };
+        indices
+            .map(|i| {
+                let kv_list: Vec<Value> = spec.file_fields.iter()
+                    .map(|field| {
+                        let file = if field.localized { &localized } else { &file };
+                        Value::KeyValue(Box::new(Value::Str(field.field_name.clone())), Box::new(file.read_field(i, field)))
+                    })
+                    .collect();
+                Value::Object(Box::new(Value::List(kv_list)))
+            })
+            .collect()
+    }
+
+    /** Rows decoded earlier in the query decoded again, in the language `localized` is reading */
+    fn reread(&self, cache: &SharedCache, table: &str, rows: &[u64], identity: &Option<Value>) -> Option<Value> {
+        let spec = self.store.unwrap().spec(table)?;
+        let decoded = self.rows(cache, spec, rows.iter().copied());
+        match identity.as_ref()? {
+            Value::Object(_) if decoded.len() == 1 => decoded.into_iter().next(),
+            Value::List(_) => Some(Value::List(decoded)),
+            Value::Iterator(_) => Some(Value::Iterator(decoded)),
+            _ => None,
+        }
+    }
+
+    /** Mods that can roll on a base with a `Weight`, from the first of their spawn weight tags the base has */
+    fn rollable_mods(&self, cache: &mut SharedCache, base: &str, types: &[String]) -> Vec<Value> {
+        let store = self.store.unwrap();
+        let field = |table: &str, name: &str| match store.spec(table).and_then(|spec| spec.field(name)) {
+            Some(field) => field.clone(),
+            None => {
+                fail(format!("rollable_mods needs {}.{} in the schema", table, name));
+            }
+        };
+        let rows = |value: Value| match value {
+            Value::List(values) => values.iter().filter_map(integer).map(|row| row as usize).collect(),
+            _ => vec![],
+        };
+
+        let tags: Vec<Value> = {
+            let (id, file) = (field("Tags", "Id"), self.file("Tags"));
+            (0..file.rows_count as u64).map(|row| file.read_field(row, &id)).collect()
+        };
+        let (mut base_tags, domain, inherits) = {
+            let bases = self.file("BaseItemTypes");
+            let id = field("BaseItemTypes", "Id");
+            let Some(row) = (0..bases.rows_count as u64).find(|row| bases.read_field(*row, &id) == Value::Str(base.to_string())) else {
+                warn!("There is no base item {}", base);
+                return vec![];
+            };
+            let base_tags: Vec<Value> = rows(bases.read_field(row, &field("BaseItemTypes", "TagsKeys"))).into_iter()
+                .filter_map(|tag| tags.get(tag).cloned())
+                .collect();
+            (base_tags, bases.read_field(row, &field("BaseItemTypes", "ModDomain")), bases.read_field(row, &field("BaseItemTypes", "InheritsFrom")))
+        };
+        // the tags of the item class are in the object files the base inherits from
+        if let Value::Str(path) = inherits {
+            match cache.object_templates.inherited(&|path| store.file_bytes(path), &path, "Base", "tag") {
+                Ok(inherited) => base_tags.extend(inherited),
+                Err(message) => warn!("{}", message),
+            }
+        }
+
+        let (mod_domain, generation) = (field("Mods", "Domain"), field("Mods", "GenerationType"));
+        let (weight_tags, weight_values) = (field("Mods", "SpawnWeight_TagsKeys"), field("Mods", "SpawnWeight_Values"));
+        let mods = self.file("Mods");
+        let weights: Vec<(u64, i64)> = (0..mods.rows_count as u64)
+            .filter(|row| mods.read_field(*row, &mod_domain) == domain)
+            .filter(|row| matches!(mods.read_field(*row, &generation), Value::Str(name) if types.contains(&name)))
+            .filter_map(|row| {
+                let Value::List(values) = mods.read_field(row, &weight_values) else { return None };
+                let weight = rows(mods.read_field(row, &weight_tags)).into_iter().zip(values)
+                    .find(|(tag, _)| tags.get(*tag).is_some_and(|tag| base_tags.contains(tag)))
+                    .and_then(|(_, weight)| integer(&weight))?;
+                Some((row, weight)).filter(|_| weight > 0)
+            })
+            .collect();
+
+        weights.into_iter()
+            .map(|(row, weight)| match self.rows_from(cache, "Mods", &[row]) {
+                Value::Object(fields) => match *fields {
+                    Value::List(mut fields) => {
+                        fields.push(Value::KeyValue(Box::new(Value::Str(String::from("Weight"))), Box::new(Value::I64(weight))));
+                        Value::Object(Box::new(Value::List(fields)))
+                    }
+                    fields => Value::Object(Box::new(fields)),
+                },
+                value => value,
+            })
+            .collect()
+    }
+
+    fn root_context(&self, identity: Option<Value>) -> TraversalContext {
+        let variables = self.variables.iter()
+            .fold(Scope::default(), |scope, (name, value)| scope.bind(name, value.clone()));
+        TraversalContext { input: identity.is_some(), identity, variables, ..TraversalContext::default() }
     }
 }
 
 /** Shared mutable data during traversal */
 #[derive(Default)]
 pub struct SharedCache {
-    variables: HashMap<String, Value>,
-    files: HashMap<String, DatFile>,
+    labels: Vec<String>,
+    breaking: Option<String>,
+    stat_descriptions: Option<StatDescriptions>,
+    object_templates: ObjectTemplates,
+    /** Set while `localized` reads the tables in each language */
+    language: Option<String>,
 }
 
 /** Local mutable data during traversal */
@@ -34,8 +167,19 @@ struct TraversalContext {
     current_field: Option<String>,
     current_file: Option<String>,
     identity: Option<Value>,
+    /** The query runs on JSON input, whose keys take precedence over tables with the same name */
+    input: bool,
+    /** Inside the body of `as`, where select keeps or drops its input as a whole like jq instead of filtering a list */
+    bound: bool,
+    /** Row in current_file of the identity, or of each of its elements, while it holds rows as they were decoded */
+    rows: Option<Vec<u64>>,
+    variables: Scope,
 }
 
+/** Variables visible to a query, later bindings shadow earlier ones */
+#[derive(Debug, Clone, Default)]
+struct Scope(Option<Arc<(String, Value, Scope)>>);
+
 trait DataTraverser<'a> {
     fn traverse(&self, context: &mut TraversalContext, cache: &mut SharedCache, parsed_terms: &[Term]) -> Value;
     fn traverse_term(&self, context: &mut TraversalContext, cache: &mut SharedCache, term: &Term) -> Value;
@@ -46,9 +190,16 @@ trait DataTraverser<'a> {
     fn index_reverse(&self, context: &mut TraversalContext, index: usize);
     fn slice(&self, context: &mut TraversalContext, from: i64, to: i64);
     fn to_iterable(&self, context: &mut TraversalContext, cache: &mut SharedCache) -> Value;
-    fn value(&self, context: &mut TraversalContext) -> Value;
+    fn value(&self, context: &mut TraversalContext, cache: &mut SharedCache) -> Value;
     fn identity(&self, context: &mut TraversalContext) -> Value;
 
+    fn stream(&self, context: &mut TraversalContext, cache: &mut SharedCache, terms: &[Term], consume: &mut dyn FnMut(Value, &mut SharedCache) -> bool);
+    fn generate(&self, context: &mut TraversalContext, cache: &mut SharedCache, terms: &[Term], limit: Option<usize>) -> Vec<Value>;
+    fn assign(&self, context: &mut TraversalContext, cache: &mut SharedCache, value: Value, path_terms: &[Term], assignment: &Assignment, terms: &[Term]) -> Value;
+    fn bind_each(&self, context: &mut TraversalContext, cache: &mut SharedCache, binding: &Term, source: &[Term], body: &[Term]) -> Value;
+    fn bind(&self, context: &mut TraversalContext, cache: &mut SharedCache, binding: &Term, value: Value, first: usize) -> usize;
+    fn destructure(&self, context: &mut TraversalContext, cache: &mut SharedCache, pattern: &Pattern, input: &Value, value: Value) -> Result<(), String>;
+
     fn enter_foreign(&self, context: &mut TraversalContext, cache: &mut SharedCache);
     fn rows_from(&self, cache: &mut SharedCache, file: &str, indices: &[u64]) -> Value;
 }
@@ -56,19 +207,74 @@ trait DataTraverser<'a> {
 impl<'a> DataTraverser<'a> for StaticContext<'a> {
     fn traverse(&self, context: &mut TraversalContext, cache: &mut SharedCache, parsed_terms: &[Term]) -> Value {
         let values: Vec<Value> = if parsed_terms.contains(&Term::PipeOperator) {
+            // outputs produced before a break still flow through the rest of the pipe
+            let mut breaking = None;
             let mut ident = context.identity();
+            let mut rows = context.rows.take();
+            let mut consumed = 0;
             for terms in parsed_terms.split(|term| matches!(term, Term::PipeOperator)) {
+                let rest = &parsed_terms[consumed..];
+                consumed += terms.len() + 1;
+                let exhausted = matches!(&ident, Value::Iterator(values) if values.is_empty());
+                if exhausted || (breaking.is_some() && ident == Value::Empty) {
+                    break;
+                }
+                // a break stops the stream at the element it happens on, so the rest of the pipe runs once per element
+                if let (Value::Iterator(items), true) = (&ident, rest.len() < parsed_terms.len() && query::breaks(rest)) {
+                    let mut outputs = Vec::new();
+                    let items_context = TraversalContext { rows: rows.take(), ..context.clone() };
+                    for (i, item) in items.clone().into_iter().enumerate() {
+                        match self.traverse(&mut items_context.element(i, item), cache, rest) {
+                            Value::Iterator(values) => outputs.extend(values),
+                            Value::Empty if cache.breaking.is_some() => {}
+                            value => outputs.push(value),
+                        }
+                        if cache.breaking.is_some() {
+                            break;
+                        }
+                    }
+                    ident = Value::Iterator(outputs);
+                    breaking = breaking.or(cache.breaking.take());
+                    break;
+                }
                 let mut c = context.clone_value(Some(ident));
-                ident = self.traverse(&mut c, cache, terms);
+                c.rows = rows.take();
+                let binding = terms.split_last()
+                    .filter(|(last, _)| matches!(last, Term::SetVariable(_) | Term::Destructure(_)));
+                ident = match binding {
+                    // variables are only visible to the rest of the pipe
+                    Some((binding, source)) => {
+                        let body = parsed_terms.get(consumed..).unwrap_or_default();
+                        self.bind_each(&mut c, cache, binding, source, body)
+                    }
+                    None => self.traverse(&mut c, cache, terms),
+                };
                 context.current_file = c.current_file;
                 context.current_field = c.current_field;
+                rows = c.rows;
+                breaking = breaking.or(cache.breaking.take());
+                if binding.is_some() {
+                    break;
+                }
             }
+            cache.breaking = breaking.or(cache.breaking.take());
+            context.rows = rows;
             vec![ident]
         } else if parsed_terms.contains(&Term::CommaSeparator) {
-            parsed_terms
-                .split(|term| matches!(term, Term::CommaSeparator))
-                .map(|terms| self.traverse(&mut context.clone(), cache, terms))
-                .collect()
+            // nested streams are concatenated
+            let mut values = Vec::new();
+            for terms in parsed_terms.split(|term| matches!(term, Term::CommaSeparator)) {
+                match self.traverse(&mut context.clone(), cache, terms) {
+                    Value::Iterator(stream) => values.extend(stream),
+                    Value::Empty if cache.breaking.is_some() => {}
+                    value => values.push(value),
+                }
+                if cache.breaking.is_some() {
+                    break;
+                }
+            }
+            context.rows = None;
+            values
         } else {
             vec![self
                 .traverse_terms_inner(context, cache, parsed_terms)
@@ -96,20 +302,22 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 Value::KeyValue(Box::new(Value::Str(key.to_string())), Box::new(asd))
             }
             Term::LookupByIndex(i) => {
+                context.rows = rows_after(context, |context| self.index(context, *i));
                 self.index(context, *i);
                 context.identity()
             }
             Term::ByIndexReverse(i) => {
+                context.rows = rows_after(context, |context| self.index_reverse(context, *i));
                 self.index_reverse(context, *i);
                 context.identity()
             }
             Term::SliceData(from, to) => {
+                context.rows = rows_after(context, |context| self.slice(context, *from, *to));
                 self.slice(context, *from, *to);
                 context.identity()
             }
             unexpected => {
-                error!("Unhandled term in query: {:?}.", unexpected);
-                process::exit(-1);
+                fail(format!("Unhandled term in query: {:?}.", unexpected));
             }
         }
     }
@@ -121,6 +329,9 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
         }
 
         for term in terms {
+            if cache.breaking.is_some() {
+                break;
+            }
             self.enter_foreign(context, cache);
 
             context.identity = match term {
@@ -129,47 +340,57 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 }
                 Term::BoolLiteral(value) => Some(Value::Bool(*value)),
                 Term::Select(lhs, op, rhs) => {
-                    let elems = self.to_iterable(context, cache);
+                    // a single value is either kept or becomes an empty stream
+                    let single = match &context.identity {
+                        None | Some(Value::Iterator(_) | Value::Empty) => false,
+                        Some(Value::List(_) | Value::Object(_)) => context.bound,
+                        Some(_) => true,
+                    };
+                    let streamed = matches!(context.identity, Some(Value::Iterator(_)));
+                    let elems = match single {
+                        true => Value::Iterator(vec![context.identity()]),
+                        false => self.to_iterable(context, cache),
+                    };
 
+                    let mut kept = Vec::new();
                     let result = iterate(elems, |v| {
+                        if cache.breaking.is_some() {
+                            kept.push(false);
+                            return None;
+                        }
                         let left = self.traverse(&mut context.clone_value(Some(v.clone())), cache, lhs);
                         let right = self.traverse(&mut context.clone_value(Some(v.clone())), cache, rhs);
 
-                        let Some(op) = op else {
-                            return match left {
-                                Value::Bool(true) => Some(v),
-                                _ => None
-                            };
-                        };
-
                         let selected = match op {
-                            Compare::Equals => left == right,
-                            Compare::NotEquals => left != right,
-                            Compare::LessThan => left < right,
-                            Compare::GreaterThan => left > right,
-                            Compare::LessThanEq => left <= right,
-                            Compare::GreaterThanEq => left >= right,
+                            None => left == Value::Bool(true),
+                            Some(op) => compare(&left, op, &right),
                         };
-                        if selected {
-                            Some(v)
-                        } else {
-                            None
-                        }
+                        kept.push(selected);
+                        selected.then_some(v)
                     });
-                    Some(result)
+                    if !single {
+                        context.rows = context.rows.take()
+                            .filter(|rows| rows.len() == kept.len())
+                            .map(|rows| rows.into_iter().zip(&kept).filter(|(_, kept)| **kept).map(|(row, _)| row).collect());
+                    }
+                    match (single, result) {
+                        (true, Value::List(mut selected)) => Some(selected.pop().unwrap_or(Value::Iterator(vec![]))),
+                        // a stream stays a stream, only a list is filtered into a list
+                        (false, Value::List(selected)) if streamed => Some(Value::Iterator(selected)),
+                        (_, result) => Some(result),
+                    }
                 }
                 Term::Contains(terms) => {
                     match self.traverse(&mut context.clone(), cache, terms) {
                         Value::Str(substr) => {
-                            let Some(value) = context.identity.take() else { return None; };
+                            let value = context.identity.take()?;
                             let Value::Str(field_string) = value else { return None; };
                             if field_string.contains(&substr) {
                                 return Some(Value::Bool(true));
                             }
                         }
                         wanted_contains => {
-                            error!("Unsupported contains type: {:?}", wanted_contains);
-                            process::exit(-1);
+                            fail(format!("Unsupported contains type: {:?}", wanted_contains));
                         }
                     }
                     Some(Value::Bool(false))
@@ -183,60 +404,303 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                     let result = match op {
                         Operation::Addition => lhs_result + rhs_result,
                         Operation::Subtraction => lhs_result - rhs_result,
-                        _ => Value::Empty,
+                        Operation::Multiplication => lhs_result * rhs_result,
+                        Operation::Division => lhs_result / rhs_result,
                     };
                     Some(result)
                 }
-                Term::SetVariable(name) => {
-                    cache.variables
-                        .insert(name.to_string(), self.identity(context));
-                    context.identity.take()
+                Term::Add => {
+                    let values = elements(context.identity(), "add");
+                    Some(values.into_iter().fold(Value::Empty, |sum, value| sum + value))
+                }
+                Term::Any(terms) => {
+                    let values = elements(context.identity(), "any");
+                    let any = values.into_iter()
+                        .any(|v| self.traverse(&mut context.clone_value(Some(v)), cache, terms).is_truthy());
+                    Some(Value::Bool(any))
                 }
-                Term::GetVariable(name) => {
-                    Some(cache.variables.get(name).unwrap_or(&Value::Empty).clone())
+                Term::All(terms) => {
+                    let values = elements(context.identity(), "all");
+                    let all = values.into_iter()
+                        .all(|v| self.traverse(&mut context.clone_value(Some(v)), cache, terms).is_truthy());
+                    Some(Value::Bool(all))
                 }
-                Term::Reduce(outer_terms, init, terms) => {
-                    // search for variables
-                    let vars: Vec<&String> = outer_terms
-                        .iter()
-                        .filter_map(|term| match term {
-                            Term::SetVariable(variable) => Some(variable),
+                Term::Flatten(terms) => {
+                    let depth = match terms.is_empty() {
+                        true => u64::MAX,
+                        false => match self.traverse(&mut context.clone(), cache, terms) {
+                            Value::U64(depth) => depth,
+                            unexpected => {
+                                fail(format!("flatten depth must be a positive number, got {}", unexpected));
+                            }
+                        }
+                    };
+                    Some(Value::List(flatten(elements(context.identity(), "flatten"), depth)))
+                }
+                Term::Range(arguments) => {
+                    let mut bounds: Vec<Value> = arguments.iter()
+                        .map(|terms| self.traverse(&mut context.clone(), cache, terms))
+                        .collect();
+                    if bounds.len() == 1 {
+                        bounds.insert(0, Value::U64(0));
+                    }
+                    if bounds.len() == 2 {
+                        bounds.push(Value::U64(1));
+                    }
+                    let [from, upto, by] = <[Value; 3]>::try_from(bounds).unwrap_or_else(|_| {
+                        fail("range takes between one and three arguments");
+                    });
+
+                    let ascending = by > Value::U64(0);
+                    let descending = by < Value::U64(0);
+                    let mut values = Vec::new();
+                    let mut current = from;
+                    while (ascending && current < upto) || (descending && current > upto) {
+                        values.push(current.clone());
+                        current = current + by.clone();
+                    }
+                    Some(Value::Iterator(values))
+                }
+                Term::Min | Term::Max => {
+                    let wanted = match term {
+                        Term::Min => Ordering::Less,
+                        _ => Ordering::Greater,
+                    };
+                    let values = elements(context.identity(), "min/max");
+                    let found = values.into_iter()
+                        .reduce(|found, v| match v.partial_cmp(&found) {
+                            Some(ordering) if ordering == wanted => v,
+                            _ => found,
+                        });
+                    Some(found.unwrap_or(Value::Empty))
+                }
+                Term::Indices(terms) => {
+                    let needle = self.traverse(&mut context.clone(), cache, terms);
+                    let indices: Vec<usize> = match (context.identity(), needle) {
+                        (Value::Str(text), Value::Str(needle)) if !needle.is_empty() => {
+                            text.char_indices()
+                                .enumerate()
+                                .filter(|(_, (byte_index, _))| text[*byte_index..].starts_with(&needle))
+                                .map(|(index, _)| index)
+                                .collect()
+                        }
+                        (Value::List(list) | Value::Iterator(list), Value::List(needle)) if !needle.is_empty() => {
+                            list.windows(needle.len())
+                                .enumerate()
+                                .filter(|(_, window)| *window == needle.as_slice())
+                                .map(|(index, _)| index)
+                                .collect()
+                        }
+                        (Value::List(list) | Value::Iterator(list), needle) => {
+                            list.iter()
+                                .enumerate()
+                                .filter(|(_, v)| **v == needle)
+                                .map(|(index, _)| index)
+                                .collect()
+                        }
+                        (Value::Empty, _) => return None,
+                        _ => vec![],
+                    };
+                    Some(Value::List(indices.into_iter().map(|i| Value::U64(i as u64)).collect()))
+                }
+                Term::Limit(count, terms) => {
+                    let count = match self.traverse(&mut context.clone(), cache, count) {
+                        Value::U64(count) => count as usize,
+                        Value::I64(_) => 0,
+                        unexpected => {
+                            fail(format!("limit count must be a number, got {}", unexpected));
+                        }
+                    };
+                    Some(Value::Iterator(self.generate(context, cache, terms, Some(count))))
+                }
+                Term::StatText(stats, values) => {
+                    let stats = self.traverse(&mut context.clone(), cache, stats);
+                    let values = self.traverse(&mut context.clone(), cache, values);
+                    // stats without an id are unused slots, like StatsKey6 on most mods
+                    let stats: Vec<(String, i64, i64)> = elements(stats, "stat_text").into_iter()
+                        .zip(elements(values, "stat_text"))
+                        .filter_map(|(stat, value)| match (stat, value) {
+                            (Value::Str(id), Value::List(range) | Value::Iterator(range)) => match range.as_slice() {
+                                [min, max] => Some((id, integer(min)?, integer(max)?)),
+                                _ => None,
+                            },
+                            (Value::Str(id), value) => integer(&value).map(|value| (id, value, value)),
                             _ => None,
                         })
                         .collect();
-                    self.traverse_terms_inner(context, cache, outer_terms);
-
-                    let initial = self.traverse(&mut context.clone_value(None), cache, init);
-                    let Some(variable) = vars.first() else {
-                        return None;
+                    let Some(store) = self.store else {
+                        fail("stat_text needs the game files, with JSON input they are only read when the query uses a dat table");
+                    };
+                    let descriptions = match &cache.stat_descriptions {
+                        Some(descriptions) => descriptions,
+                        None => match StatDescriptions::load(&|path| store.file_bytes(path), STAT_DESCRIPTIONS, store.language()) {
+                            Ok(descriptions) => cache.stat_descriptions.insert(descriptions),
+                            Err(message) => {
+                                fail(message);
+                            }
+                        },
+                    };
+                    Some(Value::List(descriptions.describe(&stats).into_iter().map(Value::Str).collect()))
+                }
+                Term::Localized(terms) => {
+                    let Some(store) = self.store else {
+                        fail("localized needs the game files, with JSON input they are only read when the query uses a dat table");
+                    };
+                    // rows decoded before are read again in each language, tables looked up inside are read in it anyway
+                    let rows = match (&context.current_file, &context.current_field, &context.rows, &cache.language) {
+                        (Some(table), None, Some(rows), None) => Some((table.clone(), rows.clone()))
+                            .filter(|(table, rows)| self.reread(cache, table, rows, &context.identity) == context.identity),
+                        _ => None,
+                    };
+                    let previous = cache.language.take();
+                    let entries = store.languages().iter()
+                        .map(|language| {
+                            cache.language = Some(language.clone());
+                            let mut context = context.clone();
+                            if let Some((table, rows)) = &rows {
+                                context.identity = self.reread(cache, table, rows, &context.identity);
+                            }
+                            let value = match self.traverse(&mut context, cache, terms) {
+                                Value::Iterator(values) => Value::List(values),
+                                value => value,
+                            };
+                            Value::KeyValue(Box::new(Value::Str(language.clone())), Box::new(value))
+                        })
+                        .collect();
+                    cache.language = previous;
+                    Some(Value::Object(Box::new(Value::List(entries))))
+                }
+                Term::First(terms) => {
+                    self.generate(context, cache, terms, Some(1)).into_iter().next()
+                }
+                Term::Last(terms) => {
+                    self.generate(context, cache, terms, None).pop()
+                }
+                Term::Until(condition, update) => {
+                    let mut current = context.identity();
+                    while !self.traverse(&mut context.clone_value(Some(current.clone())), cache, condition).is_truthy() {
+                        current = self.traverse(&mut context.clone_value(Some(current)), cache, update);
+                    }
+                    Some(current)
+                }
+                Term::While(condition, update) => {
+                    let mut values = Vec::new();
+                    let mut current = context.identity();
+                    while self.traverse(&mut context.clone_value(Some(current.clone())), cache, condition).is_truthy() {
+                        values.push(current.clone());
+                        current = self.traverse(&mut context.clone_value(Some(current)), cache, update);
+                    }
+                    Some(Value::Iterator(values))
+                }
+                Term::Comparison(lhs, op, rhs) => {
+                    let left = self.traverse(&mut context.clone(), cache, lhs);
+                    let right = self.traverse(&mut context.clone(), cache, rhs);
+                    Some(Value::Bool(compare(&left, op, &right)))
+                }
+                Term::Foreach(source, init, update, extract) => {
+                    let Some((binding, source)) = source.split_last() else {
+                        fail("foreach is missing a variable to bind each element to");
                     };
 
-                    let value = cache
-                        .variables
-                        .get(variable.as_str())
-                        .unwrap_or(&Value::Empty)
-                        .clone();
-
-                    let mut reduce_context = context.clone_value(Some(initial));
+                    let mut state = self.traverse(&mut context.clone(), cache, init);
+                    let state_context = context.clone_value(None);
+                    let mut outputs = Vec::new();
+                    self.stream(context, cache, source, &mut |item, cache| {
+                        let mut item_context = state_context.clone();
+                        self.bind(&mut item_context, cache, binding, item, 0);
+                        state = self.traverse(&mut item_context.clone_value(Some(state.clone())), cache, update);
+                        if cache.breaking.is_some() {
+                            return true;
+                        }
 
-                    let result = reduce(value, &mut |acc, v| {
-                        cache.variables.insert(variable.to_string(), v);
-                        reduce_context.identity = Some(acc);
-                        self.traverse(&mut reduce_context, cache, terms)
+                        let output = match extract.is_empty() {
+                            true => state.clone(),
+                            false => self.traverse(&mut item_context.clone_value(Some(state.clone())), cache, extract),
+                        };
+                        match output {
+                            Value::Iterator(values) => outputs.extend(values),
+                            Value::Empty if cache.breaking.is_some() => {}
+                            value => outputs.push(value),
+                        }
+                        cache.breaking.is_some()
                     });
-
+                    Some(Value::Iterator(outputs))
+                }
+                Term::Label(name, terms) => {
+                    cache.labels.push(name.to_string());
+                    let result = self.traverse(&mut context.clone(), cache, terms);
+                    cache.labels.pop();
+                    if cache.breaking.as_ref() == Some(name) {
+                        cache.breaking = None;
+                    }
                     Some(result)
                 }
-                Term::Map(terms) => {
-                    let result = iterate(self.to_iterable(context, cache), |v| {
-                        Some(self.traverse(&mut context.clone_value(Some(v)), cache, terms))
-                    });
+                Term::Break(name) => {
+                    if !cache.labels.contains(name) {
+                        fail(format!("$*label-{} is not defined", name));
+                    }
+                    cache.breaking = Some(name.to_string());
+                    None
+                }
+                Term::Assign(path_terms, assignment, terms) => {
+                    let result = match context.identity() {
+                        Value::Iterator(values) => Value::Iterator(values.into_iter()
+                            .map(|v| self.assign(context, cache, v, path_terms, assignment, terms))
+                            .collect()),
+                        value => self.assign(context, cache, value, path_terms, assignment, terms),
+                    };
                     Some(result)
                 }
+                Term::SetVariable(_) | Term::Destructure(_) => {
+                    let value = self.identity(context);
+                    self.bind(context, cache, term, value, 0);
+                    context.identity.take()
+                }
+                Term::GetVariable(name) => match context.variables.get(name) {
+                    Some(value) => Some(value.clone()),
+                    None if name == "ENV" => Some(environment()),
+                    None => {
+                        fail(format!("${} is not defined", name));
+                    }
+                },
+                Term::Reduce(source, init, terms) => {
+                    let Some((binding, source)) = source.split_last() else {
+                        fail("reduce is missing a variable to bind each element to");
+                    };
+
+                    let mut accumulator = self.traverse(&mut context.clone_value(None), cache, init);
+                    let reduce_context = context.clone_value(None);
+                    self.stream(context, cache, source, &mut |item, cache| {
+                        let mut update_context = reduce_context.clone_value(Some(accumulator.clone()));
+                        self.bind(&mut update_context, cache, binding, item, 0);
+                        accumulator = self.traverse(&mut update_context, cache, terms);
+                        cache.breaking.is_some()
+                    });
+                    Some(accumulator)
+                }
+                Term::Map(terms) => {
+                    let Value::Iterator(elements) = self.to_iterable(context, cache) else {
+                        unreachable!("to_iterable always returns an iterator");
+                    };
+                    // like [.[] | f], every output of f is an element and no output leaves it out
+                    let mut mapped = Vec::new();
+                    for (i, element) in elements.into_iter().enumerate() {
+                        if cache.breaking.is_some() {
+                            break;
+                        }
+                        match self.traverse(&mut context.element(i, element), cache, terms) {
+                            Value::Iterator(values) => mapped.extend(values),
+                            value => mapped.push(value),
+                        }
+                    }
+                    Some(Value::List(mapped))
+                }
                 Term::ObjectConstruction(obj_terms) => {
                     if let Some(value) = context.identity.take() {
+                        let mut i = 0;
                         Some(iterate(value, |v| {
-                            let output = self.traverse(&mut context.clone_value(Some(v)), cache, obj_terms);
+                            let output = self.traverse(&mut context.element(i, v), cache, obj_terms);
+                            i += 1;
                             Some(Value::Object(Box::new(output)))
                         }))
                     } else {
@@ -298,8 +762,83 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                         }
                     }
                     Value::Empty => Some(Value::U64(0)),
-                    value => unimplemented!("Unsupported type '{:?}' for 'length' operation", value)
+                    // the absolute value of a number, like jq
+                    Value::Byte(value) => Some(Value::U64(value as u64)),
+                    Value::U64(value) => Some(Value::U64(value)),
+                    Value::I64(value) => Some(Value::U64(value.unsigned_abs())),
+                    Value::F32(value) => Some(Value::F32(value.abs())),
+                    Value::F64(value) => Some(Value::F64(value.abs())),
+                    value => {
+                        fail(format!("{} has no length", value));
+                    }
                 },
+                Term::Env => Some(environment()),
+                Term::Metadata => {
+                    let Some(store) = self.store else {
+                        fail("metadata needs the game files, with JSON input they are only read when the query uses a dat table");
+                    };
+                    let mut resolve = |value: Value| match value {
+                        Value::Str(path) => cache.object_templates.resolve(&|path| store.file_bytes(path), &path).unwrap_or_else(|message| {
+                            warn!("{}", message);
+                            Value::Empty
+                        }),
+                        Value::Empty => Value::Empty,
+                        unexpected => {
+                            fail(format!("metadata needs the path of an object file, got {}", unexpected));
+                        }
+                    };
+                    match context.identity() {
+                        Value::Iterator(values) => Some(Value::Iterator(values.into_iter().map(&mut resolve).collect())),
+                        value => Some(resolve(value)),
+                    }
+                }
+                Term::RollableMods(base, types) => {
+                    if self.store.is_none() {
+                        fail("rollable_mods needs the game files, with JSON input they are only read when the query uses a dat table");
+                    }
+                    let base = match self.traverse(&mut context.clone(), cache, base) {
+                        Value::Str(id) => id,
+                        unexpected => {
+                            fail(format!("rollable_mods needs the Id of a base item, got {}", unexpected));
+                        }
+                    };
+                    // affixes unless other generation types are asked for
+                    let types = match types.is_empty() {
+                        true => vec![Value::Str(String::from("PREFIX")), Value::Str(String::from("SUFFIX"))],
+                        false => match self.traverse(&mut context.clone(), cache, types) {
+                            Value::List(types) | Value::Iterator(types) => types,
+                            value => vec![value],
+                        },
+                    };
+                    let types: Vec<String> = types.into_iter()
+                        .map(|value| match value {
+                            Value::Str(name) => name.to_uppercase(),
+                            unexpected => {
+                                fail(format!("rollable_mods needs generation types like \"PREFIX\", got {}", unexpected));
+                            }
+                        })
+                        .collect();
+                    let mods = self.rollable_mods(cache, &base, &types);
+                    context.current_file = Some(String::from("Mods"));
+                    context.current_field = None;
+                    Some(Value::List(mods))
+                }
+                Term::ReadFile => {
+                    let Some(store) = self.store else {
+                        fail("read_file needs the game files, with JSON input they are only read when the query uses a dat table");
+                    };
+                    let read = |value: Value| match value {
+                        Value::Str(path) => store.file_bytes(&path).map_or(Value::Empty, file_contents),
+                        Value::Empty => Value::Empty,
+                        unexpected => {
+                            fail(format!("read_file needs a path, got {}", unexpected));
+                        }
+                    };
+                    match context.identity() {
+                        Value::Iterator(values) => Some(Value::Iterator(values.into_iter().map(read).collect())),
+                        value => Some(read(value)),
+                    }
+                }
                 Term::Keys => match context.identity() {
                     Value::Object(data) => {
                         match *data {
@@ -318,14 +857,26 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                             _ => None
                         }
                     }
-                    value => unimplemented!("Unsupported type '{:?}' for 'keys' operation", value)
+                    Value::List(list) => Some(Value::List((0..list.len() as u64).map(Value::U64).collect())),
+                    value => {
+                        fail(format!("{} has no keys", value));
+                    }
                 },
-                Term::Key(terms) => {
-                    Some(self.traverse(context, cache, terms))
+                Term::Key(terms) | Term::Group(terms) => {
+                    let scope = context.variables.clone();
+                    let result = self.traverse(context, cache, terms);
+                    context.variables = scope;
+                    Some(result)
                 }
                 Term::StringLiteral(text) => {
                     Some(Value::Str(text.to_string()))
                 }
+                Term::Format(format) => match context.identity() {
+                    Value::Iterator(values) => Some(Value::Iterator(values.into_iter()
+                        .map(|value| format::format(value, format))
+                        .collect())),
+                    value => Some(format::format(value, format)),
+                },
                 Term::Transpose => match context.identity() {
                     Value::List(values) => {
                         trace!("transpose input {:?}", values);
@@ -354,8 +905,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                         Some(Value::List(outer))
                     }
                     unexpected => {
-                        error!("Transpose is only supported on lists. Attempted on type: {}.", unexpected);
-                        process::exit(-1);
+                        fail(format!("Transpose is only supported on lists. Attempted on type: {}.", unexpected));
                     }
                 },
                 Term::UnsignedNumber(value) => {
@@ -366,6 +916,12 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 }
                 _ => Some(self.traverse_term(context, cache, term))
             };
+            // these only pick rows out of rows, anything else makes new values
+            let picks_rows = matches!(term, Term::NoOperation | Term::Identity | Term::Iterator | Term::Select(..) |
+                Term::LookupByName(_) | Term::LookupByIndex(_) | Term::ByIndexReverse(_) | Term::SliceData(..));
+            if !picks_rows {
+                context.rows = None;
+            }
         }
 
         context.identity.take()
@@ -375,35 +931,22 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
         trace!("entered {}", name);
 
         let spec: Option<&FileSpec> = self.store.and_then(|s| s.spec_by_export(name))
+            .filter(|_| !(context.input && matches!(context.identity, Some(Value::Object(_)))))
             .or_else(|| self.store.and_then(|s| s.spec_by_export(context.current_file.as_ref().unwrap_or(&"".to_string()))));
 
         self.enter_foreign(context, cache);
         if let (Some(spec), None) = (spec, &context.current_file) {
             // generate initial values
-            let file = cache.files.entry(spec.file_name.to_string()).or_insert_with(|| self.store.unwrap().file_by_filename(&spec.file_name).unwrap());
-
-            let values: Vec<Value> = (0..file.rows_count)
-                .map(|i| {
-                    let kv_list: Vec<Value> = spec
-                        .file_fields
-                        .iter()
-                        .map(|field| {
-                            Value::KeyValue(
-                                Box::new(Value::Str(field.field_name.clone())),
-                                Box::new(file.read_field(i as u64, field)),
-                            )
-                        })
-                        .collect();
-                    Value::Object(Box::new(Value::List(kv_list)))
-                })
-                .collect();
+            let values = self.rows(cache, spec, 0..self.file(&spec.file_name).rows_count as u64);
 
             context.current_field = None;
             context.current_file = Some(spec.file_name.to_string());
+            context.rows = Some((0..values.len() as u64).collect());
             context.identity = Some(Value::List(values));
         } else {
             context.current_field = Some(name.to_string());
-            context.identity = Some(self.value(context));
+            context.rows = None;
+            context.identity = Some(self.value(context, cache));
         }
     }
 
@@ -420,12 +963,13 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
         let value = context.identity();
         context.identity = match value {
             Value::List(list) => {
-                let index = list.len() - index;
-                list.into_iter().nth(index)
+                list.len().checked_sub(index)
+                    .and_then(|index| list.into_iter().nth(index))
             }
             Value::Str(str) => {
-                let index = str.chars().count() - index;
-                str.chars().nth(index).map(|value| Value::Str(value.to_string()))
+                str.chars().count().checked_sub(index)
+                    .and_then(|index| str.chars().nth(index))
+                    .map(|value| Value::Str(value.to_string()))
             }
             _ => None,
         };
@@ -457,8 +1001,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 }
             }
             unexpected => {
-                error!("Type {unexpected} cannot be sliced/indexed");
-                process::exit(-1);
+                fail(format!("Type {unexpected} cannot be sliced/indexed"));
             }
         };
     }
@@ -474,21 +1017,19 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 let fields = match *content {
                     Value::List(fields) | Value::Iterator(fields) => fields,
                     unexpected => {
-                        error!("Type {unexpected} cannot be iterated over");
-                        process::exit(-1);
+                        fail(format!("Type {unexpected} cannot be iterated over"));
                     }
                 };
                 Value::Iterator(fields)
             }
             Value::Empty => Value::Iterator(Vec::with_capacity(0)),
             unexpected => {
-                error!("Type {unexpected} cannot be iterated over");
-                process::exit(-1);
+                fail(format!("Type {unexpected} cannot be iterated over"));
             }
         }
     }
 
-    fn value(&self, context: &mut TraversalContext) -> Value {
+    fn value(&self, context: &mut TraversalContext, cache: &mut SharedCache) -> Value {
         if context.identity.is_none() {
             return Value::Empty;
         }
@@ -516,8 +1057,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                         }
                     }
                     unexpected => {
-                        error!("failed to extract Value::Object. Object contained {}", unexpected);
-                        process::exit(-1);
+                        fail(format!("failed to extract Value::Object. Object contained {}", unexpected));
                     }
                 }
             }
@@ -536,8 +1076,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                             let obj = match *elements {
                                 Value::List(fields) | Value::Iterator(fields) => fields,
                                 unexpected => {
-                                    error!("Type {unexpected} unexpected in Value::Object");
-                                    process::exit(-1);
+                                    fail(format!("Type {unexpected} unexpected in Value::Object"));
                                 }
                             };
 
@@ -551,41 +1090,25 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                                         }
                                     }
                                     unexpected => {
-                                        error!("failed to extract Value::Object. Object contained {}", unexpected);
-                                        process::exit(-1);
+                                        fail(format!("failed to extract Value::Object. Object contained {}", unexpected));
                                     }
                                 }
                             }
                             first
                         }
                         unexpected => {
-                            error!("Unable to to iterate over {}.", unexpected);
-                            process::exit(-1);
+                            fail(format!("Unable to to iterate over {}.", unexpected));
                         }
                     };
                     result.push(item);
                 }
 
-                Value::List(result)
+                Value::Iterator(result)
             }
             Value::U64(i) => {
                 let current = context.current_file.as_ref().unwrap();
                 let spec = self.store.unwrap().spec(current).unwrap();
-                let file = self.store.unwrap().file_by_filename(current).unwrap();
-
-                // TODO: extract to function
-                let kv_list: Vec<Value> = spec
-                    .file_fields
-                    .iter()
-                    .map(move |field| {
-                        Value::KeyValue(
-                            Box::new(Value::Str(field.field_name.clone())),
-                            Box::new(file.read_field(i, field)),
-                        )
-                    })
-                    .collect();
-
-                Value::Object(Box::new(Value::List(kv_list)))
+                self.rows(cache, spec, std::iter::once(i)).pop().unwrap_or(Value::Empty)
             }
             _ => Value::Empty,
         }
@@ -595,6 +1118,257 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
         context.identity.clone().unwrap_or(Value::Empty)
     }
 
+    // Feeds the outputs of a generator to `consume` one streamed element at a time, until it returns true.
+    // Only `.Table[] | ...` decodes its rows one by one, any other source is evaluated in full before its elements are streamed.
+    fn stream(&self, context: &mut TraversalContext, cache: &mut SharedCache, terms: &[Term], consume: &mut dyn FnMut(Value, &mut SharedCache) -> bool) {
+        let stream = terms.iter()
+            .position(|term| *term == Term::Iterator)
+            .filter(|_| !terms.contains(&Term::CommaSeparator));
+        let Some(position) = stream else {
+            let outputs = match self.traverse(&mut context.clone(), cache, terms) {
+                Value::Iterator(values) => values,
+                Value::Empty => vec![],
+                value => vec![value],
+            };
+            for output in outputs {
+                if consume(output, cache) {
+                    break;
+                }
+            }
+            return;
+        };
+
+        let (source, rest) = (&terms[..position], &terms[position + 1..]);
+        let rest = rest.strip_prefix(&[Term::PipeOperator]).unwrap_or(rest);
+
+        let mut source_context = context.clone();
+        let table = match source.iter().filter(|term| **term != Term::Identity).collect::<Vec<_>>()[..] {
+            [Term::LookupByName(name)] if context.current_file.is_none() => self.store.and_then(|s| s.spec_by_export(name)),
+            _ => None,
+        };
+
+        // rows are decoded lazily when iterating a table, anything else is evaluated up front
+        let items = match table {
+            Some(spec) => {
+                source_context.current_field = None;
+                source_context.current_file = Some(spec.file_name.to_string());
+                None
+            }
+            None => {
+                if !source.is_empty() {
+                    let value = self.traverse(&mut source_context, cache, source);
+                    source_context.identity = Some(value);
+                }
+                match self.to_iterable(&mut source_context, cache) {
+                    Value::Iterator(items) => Some(items),
+                    _ => unreachable!("to_iterable always returns an iterator"),
+                }
+            }
+        };
+
+        let mut emit = |item: Value, row: Option<u64>, cache: &mut SharedCache| {
+            let outputs = match rest.is_empty() {
+                true => vec![item],
+                false => {
+                    // a list is a single output, only a stream is several
+                    let item_context = &mut source_context.clone_value(Some(item));
+                    item_context.rows = row.map(|row| vec![row]);
+                    match self.traverse(item_context, cache, rest) {
+                        Value::Iterator(values) => values,
+                        Value::Empty => vec![],
+                        value => vec![value],
+                    }
+                }
+            };
+            outputs.into_iter().any(|output| consume(output, cache)) || cache.breaking.is_some()
+        };
+
+        match (items, table) {
+            (Some(items), _) => {
+                let rows = source_context.rows.clone().filter(|rows| rows.len() == items.len());
+                for (i, item) in items.into_iter().enumerate() {
+                    if emit(item, rows.as_ref().map(|rows| rows[i]), cache) {
+                        break;
+                    }
+                }
+            }
+            (None, Some(spec)) => {
+                let rows_count = self.file(&spec.file_name).rows_count;
+                for i in 0..rows_count as u64 {
+                    let row = self.rows_from(cache, &spec.file_name, &[i]);
+                    if emit(row, Some(i), cache) {
+                        break;
+                    }
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    fn generate(&self, context: &mut TraversalContext, cache: &mut SharedCache, terms: &[Term], limit: Option<usize>) -> Vec<Value> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut outputs = Vec::new();
+        if limit > 0 {
+            self.stream(context, cache, terms, &mut |output, _| {
+                outputs.push(output);
+                outputs.len() >= limit
+            });
+        }
+        outputs
+    }
+
+    // Values are owned copies, rows read from dat files are never modified in place
+    fn assign(&self, context: &mut TraversalContext, cache: &mut SharedCache, value: Value, path_terms: &[Term], assignment: &Assignment, terms: &[Term]) -> Value {
+        let paths = path::paths(&value, path_terms, &mut |select, current| {
+            let mut select_context = context.clone_value(Some(Value::Iterator(vec![current])));
+            let selected = self.traverse(&mut select_context, cache, std::slice::from_ref(select));
+            matches!(selected, Value::Iterator(values) if !values.is_empty())
+        });
+
+        // everything except |= evaluates the right hand side against the input
+        let rhs = match assignment {
+            Assignment::Update => Value::Empty,
+            _ => self.traverse(&mut context.clone_value(Some(value.clone())), cache, terms),
+        };
+
+        paths.iter().fold(value, |result, path| {
+            let current = path::get(&result, path);
+            let updated = match assignment {
+                Assignment::Update => {
+                    let mut update_context = context.clone_value(Some(current));
+                    if let Some(PathElement::Key(field)) = path.last() {
+                        update_context.current_field = Some(field.to_string());
+                    }
+                    self.traverse(&mut update_context, cache, terms)
+                }
+                Assignment::Set => rhs.clone(),
+                Assignment::Addition => current + rhs.clone(),
+                Assignment::Subtraction => current - rhs.clone(),
+                Assignment::Alternative if current.is_truthy() => current,
+                Assignment::Alternative => rhs.clone(),
+            };
+            path::set(result, path, updated)
+        })
+    }
+
+    /** `source as $name | body`, the body runs once per output of source with the original input */
+    fn bind_each(&self, context: &mut TraversalContext, cache: &mut SharedCache, binding: &Term, source: &[Term], body: &[Term]) -> Value {
+        let inputs = match context.identity() {
+            Value::Iterator(values) => values,
+            value => vec![value],
+        };
+
+        let alternatives = match binding {
+            Term::Destructure(patterns) => patterns.len(),
+            _ => 1,
+        };
+        let run = |context: &mut TraversalContext, cache: &mut SharedCache| match body.is_empty() {
+            true => context.identity(),
+            false => self.traverse(context, cache, body),
+        };
+
+        let mut outputs = Vec::new();
+        for input in inputs {
+            let input_context = context.clone_value(Some(input));
+            self.stream(&mut input_context.clone(), cache, source, &mut |item, cache| {
+                // like jq, a body failing with one `?//` alternative is retried with the next, the last one's error stops the query
+                let mut first = 0;
+                let output = loop {
+                    let mut body_context = TraversalContext { bound: true, ..input_context.clone() };
+                    let bound = self.bind(&mut body_context, cache, binding, item.clone(), first);
+                    if bound + 1 == alternatives {
+                        break run(&mut body_context, cache);
+                    }
+                    let labels = cache.labels.len();
+                    match catch_errors(|| run(&mut body_context, cache)) {
+                        Ok(output) => break output,
+                        Err(message) => {
+                            debug!("Trying the next alternative after: {}", message);
+                            cache.labels.truncate(labels);
+                            cache.breaking = None;
+                            first = bound + 1;
+                        }
+                    }
+                };
+                match output {
+                    Value::Iterator(values) => outputs.extend(values),
+                    Value::Empty if cache.breaking.is_some() => {}
+                    value => outputs.push(value),
+                }
+                cache.breaking.is_some()
+            });
+            if cache.breaking.is_some() {
+                break;
+            }
+        }
+
+        match outputs.len() {
+            1 => outputs.pop().unwrap(),
+            _ => Value::Iterator(outputs),
+        }
+    }
+
+    /** Binds a value to the variables of `as`, each `?//` alternative from `first` is tried in order until one matches, returning it */
+    fn bind(&self, context: &mut TraversalContext, cache: &mut SharedCache, binding: &Term, value: Value, first: usize) -> usize {
+        let patterns = match binding {
+            Term::SetVariable(name) => {
+                context.variables = context.variables.bind(name, value);
+                return 0;
+            }
+            Term::Destructure(patterns) => patterns,
+            unexpected => {
+                fail(format!("Expected a variable binding, got {:?}", unexpected));
+            }
+        };
+
+        // variables of every alternative are defined, the unmatched ones are null
+        let scope = patterns.iter()
+            .flat_map(Pattern::variables)
+            .fold(context.variables.clone(), |scope, name| scope.bind(name, Value::Empty));
+
+        let mut failure = String::new();
+        for (i, pattern) in patterns.iter().enumerate().skip(first) {
+            let mut pattern_context = context.clone();
+            pattern_context.variables = scope.clone();
+            match self.destructure(&mut pattern_context, cache, pattern, &value, value.clone()) {
+                Ok(()) => {
+                    context.variables = pattern_context.variables;
+                    return i;
+                }
+                Err(message) => failure = message,
+            }
+        }
+        fail(failure);
+    }
+
+    fn destructure(&self, context: &mut TraversalContext, cache: &mut SharedCache, pattern: &Pattern, input: &Value, value: Value) -> Result<(), String> {
+        match (pattern, value) {
+            (Pattern::Variable(name), value) => {
+                context.variables = context.variables.bind(name, value);
+            }
+            (Pattern::Array(patterns), value @ (Value::List(_) | Value::Iterator(_) | Value::Empty)) => {
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let element = path::get(&value, &[PathElement::Index(i)]);
+                    self.destructure(context, cache, pattern, input, element)?;
+                }
+            }
+            (Pattern::Object(entries), value @ (Value::Object(_) | Value::Empty)) => {
+                for (key, pattern) in entries {
+                    // keys are evaluated against the bound value and can refer to earlier variables
+                    let key = match self.traverse(&mut context.clone_value(Some(input.clone())), cache, key) {
+                        Value::Str(key) => key,
+                        unexpected => return Err(format!("Cannot index {} with {}", value, unexpected)),
+                    };
+                    let field = path::get(&value, &[PathElement::Key(key)]);
+                    self.destructure(context, cache, pattern, input, field)?;
+                }
+            }
+            (Pattern::Array(_), value) => return Err(format!("Cannot index {} with number", value)),
+            (Pattern::Object(_), value) => return Err(format!("Cannot index {} with string", value)),
+        }
+        Ok(())
+    }
+
     fn enter_foreign(&self, context: &mut TraversalContext, cache: &mut SharedCache) {
         let current_spec: Option<&FileSpec> = context
             .current_file.as_ref()
@@ -619,6 +1393,14 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                 Value::List(items) => Value::Iterator(items),
                 _ => value,
             };
+            context.rows = match &value {
+                Value::U64(id) => Some(vec![*id]),
+                Value::Iterator(ids) => ids.iter().map(|id| match id {
+                    Value::U64(id) => Some(*id),
+                    _ => None,
+                }).collect(),
+                _ => None,
+            };
 
             let result = iterate(value, |v| {
                 let ids: Vec<u64> = match v {
@@ -627,8 +1409,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                     Value::U64(id) => vec![Value::U64(id)],
                     Value::Empty => vec![],
                     unexpected => {
-                        error!("Not a valid id for foreign key {}.", unexpected);
-                        process::exit(-1);
+                        fail(format!("Not a valid id for foreign key {}.", unexpected));
                     }
                 }
                     .iter()
@@ -636,8 +1417,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
                         Value::U64(i) => Some(*i),
                         Value::List(_) => None,
                         unexpected => {
-                            error!("Unexpected value {} in enter_foreign.", unexpected);
-                            process::exit(-1);
+                            fail(format!("Unexpected value {} in enter_foreign.", unexpected));
                         }
                     })
                     .collect();
@@ -654,25 +1434,7 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
 
     fn rows_from(&self, cache: &mut SharedCache, filepath: &str, indices: &[u64]) -> Value {
         let foreign_spec = self.store.unwrap().spec(filepath).unwrap();
-
-        let file = cache.files.entry(filepath.to_string()).or_insert_with(|| self.store.unwrap().file_by_filename(filepath).unwrap());
-
-        let values: Vec<Value> = indices
-            .iter()
-            .map(|i| {
-                let kv_list: Vec<Value> = foreign_spec
-                    .file_fields
-                    .iter()
-                    .map(|field| {
-                        Value::KeyValue(
-                            Box::new(Value::Str(field.field_name.clone())),
-                            Box::new(file.read_field(*i, field)),
-                        )
-                    })
-                    .collect();
-                Value::Object(Box::new(Value::List(kv_list)))
-            })
-            .collect();
+        let values = self.rows(cache, foreign_spec, indices.iter().copied());
 
         if values.len() > 1 {
             Value::List(values)
@@ -682,16 +1444,82 @@ impl<'a> DataTraverser<'a> for StaticContext<'a> {
     }
 }
 
+// the same indexing done on the row numbers of a list of rows tells the rows it keeps
+fn rows_after(context: &TraversalContext, index: impl Fn(&mut TraversalContext)) -> Option<Vec<u64>> {
+    let rows = context.rows.as_ref().filter(|_| matches!(context.identity, Some(Value::List(_))))?;
+    let mut rows_context = TraversalContext { identity: Some(Value::List(rows.iter().map(|row| Value::U64(*row)).collect())), ..TraversalContext::default() };
+    index(&mut rows_context);
+    match rows_context.identity? {
+        Value::U64(row) => Some(vec![row]),
+        Value::List(rows) => rows.iter().map(|row| match row {
+            Value::U64(row) => Some(*row),
+            _ => None,
+        }).collect(),
+        _ => None,
+    }
+}
+
+fn compare(left: &Value, op: &Compare, right: &Value) -> bool {
+    match op {
+        Compare::Equals => left == right,
+        Compare::NotEquals => left != right,
+        Compare::LessThan => left < right,
+        Compare::GreaterThan => left > right,
+        Compare::LessThanEq => left <= right,
+        Compare::GreaterThanEq => left >= right,
+    }
+}
+
 impl TraversalContext {
     pub fn clone_value(&self, ident: Option<Value>) -> Self {
         Self {
             current_field: self.current_field.clone(),
             current_file: self.current_file.clone(),
             identity: ident,
+            input: self.input,
+            bound: self.bound,
+            rows: None,
+            variables: self.variables.clone(),
         }
     }
 
+    /** A context for one element of the identity, which keeps the row of that element */
+    fn element(&self, index: usize, value: Value) -> Self {
+        let mut context = self.clone_value(Some(value));
+        context.rows = self.rows.as_ref().and_then(|rows| rows.get(index)).map(|row| vec![*row]);
+        context
+    }
+
     pub fn identity(&mut self) -> Value {
         self.identity.take().unwrap_or(Value::Empty)
     }
 }
+
+impl Scope {
+    fn bind(&self, name: &str, value: Value) -> Scope {
+        Scope(Some(Arc::new((name.to_string(), value, self.clone()))))
+    }
+
+    fn get(&self, name: &str) -> Option<&Value> {
+        let mut scope = self;
+        while let Some(binding) = &scope.0 {
+            let (bound, value, parent) = binding.as_ref();
+            if bound == name {
+                return Some(value);
+            }
+            scope = parent;
+        }
+        None
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Byte(value) => Some(*value as i64),
+        Value::U64(value) => Some(*value as i64),
+        Value::I64(value) => Some(*value),
+        Value::F32(value) => Some(value.round() as i64),
+        Value::F64(value) => Some(value.round() as i64),
+        _ => None,
+    }
+}
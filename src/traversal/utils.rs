@@ -1,5 +1,57 @@
+use std::cell::Cell;
+use std::panic;
+use std::process;
+use std::sync::Once;
+
+use log::*;
+
+use crate::dat::stat_description::decode;
 use crate::Value;
 
+/** An error in a query, only caught inside `catch_errors` */
+struct QueryError(String);
+
+thread_local! {
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+}
+
+/** Ends the program with the message, unless the query runs inside `catch_errors` */
+pub fn fail(message: impl Into<String>) -> ! {
+    let message = message.into();
+    if CATCHING.with(Cell::get) == 0 {
+        error!("{}", message);
+        process::exit(-1);
+    }
+    panic::panic_any(QueryError(message))
+}
+
+/** Runs `f`, returning the message instead when the query in it fails */
+pub fn catch_errors<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    // caught errors are not panics to report
+    static QUIET: Once = Once::new();
+    QUIET.call_once(|| {
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if info.payload().downcast_ref::<QueryError>().is_none() {
+                report(info);
+            }
+        }));
+    });
+
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+    result.map_err(|payload| match payload.downcast::<QueryError>() {
+        Ok(error) => error.0,
+        // operations that aren't implemented panic, the hook has reported where
+        Err(payload) => match (payload.downcast_ref::<String>(), payload.downcast_ref::<&str>()) {
+            (Some(message), _) => message.clone(),
+            (_, Some(message)) => message.to_string(),
+            _ => String::from("The query panicked"),
+        },
+    })
+}
+
 pub fn iterate<F>(value: Value, mut action: F) -> Value
     where
         F: FnMut(Value) -> Option<Value> + Send + Sync,
@@ -15,13 +67,40 @@ pub fn iterate<F>(value: Value, mut action: F) -> Value
     }
 }
 
-pub fn reduce<F>(initial: Value, mut action: F) -> Value
-    where
-        F: FnMut(Value, Value) -> Value,
-{
-    match initial {
-        Value::Iterator(elements) =>
-            elements.into_iter().reduce(action).unwrap_or(Value::Empty),
-        _ => action(Value::Empty, initial)
+pub fn elements(value: Value, operation: &str) -> Vec<Value> {
+    match value {
+        Value::List(elements) | Value::Iterator(elements) => elements,
+        Value::Empty => vec![],
+        unexpected => {
+            fail(format!("Unsupported type '{}' for '{}' operation", unexpected, operation));
+        }
+    }
+}
+
+pub fn flatten(values: Vec<Value>, depth: u64) -> Vec<Value> {
+    values.into_iter()
+        .flat_map(|value| match value {
+            Value::List(inner) | Value::Iterator(inner) if depth > 0 => flatten(inner, depth - 1),
+            value => vec![value],
+        })
+        .collect()
+}
+
+/** Environment variables as an object, for `$ENV` and `env` */
+pub fn environment() -> Value {
+    let entries = std::env::vars()
+        .map(|(key, value)| Value::KeyValue(Box::new(Value::Str(key)), Box::new(Value::Str(value))))
+        .collect();
+    Value::Object(Box::new(Value::List(entries)))
+}
+
+/** Text files as a string, UTF-16 when they start with a byte order mark, anything else as a list of bytes */
+pub fn file_contents(bytes: Vec<u8>) -> Value {
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return Value::Str(decode(&bytes));
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Value::Str(text),
+        Err(error) => Value::List(error.into_bytes().into_iter().map(Value::Byte).collect()),
     }
-}
\ No newline at end of file
+}
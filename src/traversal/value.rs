@@ -1,10 +1,9 @@
-use log::*;
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use std::{fmt, ops};
 use std::cmp::Ordering;
 use std::fmt::Formatter;
 use std::ops::Deref;
-use std::process;
+use crate::traversal::utils::fail;
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -13,6 +12,8 @@ pub enum Value {
     U64(u64),
     I64(i64),
     F32(f32),
+    /** Floats of JSON input, kept at full precision */
+    F64(f64),
     List(Vec<Value>),
     Iterator(Vec<Value>),
     KeyValue(Box<Value>, Box<Value>),
@@ -28,7 +29,7 @@ impl fmt::Display for Value {
             Value::Byte(_) => write!(f, "Byte"),
             Value::U64(_) => write!(f, "Int"),
             Value::I64(_) => write!(f, "Int"),
-            Value::F32(_) => write!(f, "Float"),
+            Value::F32(_) | Value::F64(_) => write!(f, "Float"),
             Value::List(list) => write!(f, "List(length = {})", list.len()),
             Value::Iterator(_) => write!(f, "Iterator"),
             Value::KeyValue(_, _) => write!(f, "KeyValue"),
@@ -46,10 +47,10 @@ impl ops::Add<Value> for Value {
         use Value::*;
         match (self, rhs) {
             (Empty, Empty) => Empty,
+            (Empty, rhs) => rhs,
+            (lhs, Empty) => lhs,
             (Str(lhs), Str(rhs)) => Str(format!("{}{}", lhs, rhs)),
-            (U64(lhs), U64(rhs)) => U64(lhs + rhs),
-            (I64(lhs), I64(rhs)) => I64(lhs + rhs),
-            (Byte(lhs), Byte(rhs)) => Byte(lhs + rhs),
+            (lhs, rhs) if lhs.is_number() && rhs.is_number() => numeric(lhs, rhs, "+", i128::checked_add, |lhs, rhs| lhs + rhs),
             (List(lhs), List(rhs)) => List([&lhs[..], &rhs[..]].concat()),
             (Iterator(lhs), Iterator(rhs)) => Iterator([&lhs[..], &rhs[..]].concat()),
             (Object(lhs), Object(rhs)) => {
@@ -83,8 +84,7 @@ impl ops::Add<Value> for Value {
                 )))
             }
             (lhs, rhs) => {
-                error!("Operation not supported: {} + {}", lhs, rhs);
-                process::exit(-1);
+                fail(format!("Operation not supported: {} + {}", lhs, rhs));
             }
         }
     }
@@ -97,6 +97,65 @@ impl Value {
             _ => None
         }
     }
+
+    /** An object of the entries in their order */
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Value)>) -> Value {
+        let entries = entries.into_iter()
+            .map(|(key, value)| Value::KeyValue(Box::new(Value::Str(key.into())), Box::new(value)))
+            .collect();
+        Value::Object(Box::new(Value::List(entries)))
+    }
+
+    /** Everything except Empty and false is considered true, same as jq */
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Empty | Value::Bool(false))
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Value::Byte(_) | Value::U64(_) | Value::I64(_) | Value::F32(_) | Value::F64(_))
+    }
+
+    fn integer(&self) -> Option<i128> {
+        match self {
+            Value::Byte(value) => Some(*value as i128),
+            Value::U64(value) => Some(*value as i128),
+            Value::I64(value) => Some(*value as i128),
+            _ => None,
+        }
+    }
+
+    fn float(&self) -> f64 {
+        match self {
+            Value::F32(value) => *value as f64,
+            Value::F64(value) => *value,
+            value => value.integer().unwrap_or_default() as f64,
+        }
+    }
+}
+
+// f32 columns stay f32, the result is only as precise as the wider side
+fn float(lhs: &Value, rhs: &Value, result: f64) -> Value {
+    match matches!(lhs, Value::F64(_)) || matches!(rhs, Value::F64(_)) {
+        true => Value::F64(result),
+        false => Value::F32(result as f32),
+    }
+}
+
+// integers stay unsigned unless either side is signed or the result is negative, floats win over integers
+fn numeric(lhs: Value, rhs: Value, symbol: &str, integers: fn(i128, i128) -> Option<i128>, floats: fn(f64, f64) -> f64) -> Value {
+    let (Some(lhs_integer), Some(rhs_integer)) = (lhs.integer(), rhs.integer()) else {
+        return float(&lhs, &rhs, floats(lhs.float(), rhs.float()));
+    };
+    let unsigned = !matches!(lhs, Value::I64(_)) && !matches!(rhs, Value::I64(_));
+    let result = integers(lhs_integer, rhs_integer);
+    match result {
+        Some(result) if unsigned && result >= 0 && result <= u64::MAX as i128 => Value::U64(result as u64),
+        Some(result) if result >= i64::MIN as i128 && result <= i64::MAX as i128 => Value::I64(result as i64),
+        Some(result) if result >= 0 && result <= u64::MAX as i128 => Value::U64(result as u64),
+        _ => {
+            fail(format!("Integer overflow: {} {} {}", lhs_integer, symbol, rhs_integer));
+        }
+    }
 }
 
 impl ops::Sub<Value> for Value {
@@ -106,15 +165,50 @@ impl ops::Sub<Value> for Value {
         use Value::*;
         match (self, rhs) {
             (Empty, Empty) => Empty,
-            (U64(lhs), U64(rhs)) => U64(lhs - rhs),
-            (I64(lhs), I64(rhs)) => I64(lhs - rhs),
-            (Byte(lhs), Byte(rhs)) => Byte(lhs - rhs),
+            (lhs, rhs) if lhs.is_number() && rhs.is_number() => numeric(lhs, rhs, "-", i128::checked_sub, |lhs, rhs| lhs - rhs),
             (List(lhs), List(rhs)) => {
                 List(lhs.into_iter().filter(|e| !rhs.contains(e)).collect())
             },
             (lhs, rhs) => {
-                error!("Subtraction not supported: {} - {}", lhs, rhs);
-                process::exit(-1);
+                fail(format!("Subtraction not supported: {} - {}", lhs, rhs));
+            }
+        }
+    }
+}
+
+impl ops::Mul<Value> for Value {
+    type Output = Value;
+
+    fn mul(self, rhs: Value) -> Value {
+        use Value::*;
+        match (self, rhs) {
+            (Empty, Empty) => Empty,
+            (lhs, rhs) if lhs.is_number() && rhs.is_number() => numeric(lhs, rhs, "*", i128::checked_mul, |lhs, rhs| lhs * rhs),
+            (lhs, rhs) => {
+                fail(format!("Multiplication not supported: {} * {}", lhs, rhs));
+            }
+        }
+    }
+}
+
+impl ops::Div<Value> for Value {
+    type Output = Value;
+
+    // integers stay integers when evenly divisible, like jq prints them
+    fn div(self, rhs: Value) -> Value {
+        use Value::*;
+        match (self, rhs) {
+            (Empty, Empty) => Empty,
+            (_, U64(0) | I64(0)) => {
+                fail("Division by zero");
+            }
+            (U64(lhs), U64(rhs)) if lhs % rhs == 0 => U64(lhs / rhs),
+            (I64(lhs), I64(rhs)) if lhs % rhs == 0 => I64(lhs / rhs),
+            (U64(lhs), I64(rhs)) if lhs as i64 % rhs == 0 => I64(lhs as i64 / rhs),
+            (I64(lhs), U64(rhs)) if lhs % rhs as i64 == 0 => I64(lhs / rhs as i64),
+            (lhs, rhs) if lhs.is_number() && rhs.is_number() => float(&lhs, &rhs, lhs.float() / rhs.float()),
+            (lhs, rhs) => {
+                fail(format!("Division not supported: {} / {}", lhs, rhs));
             }
         }
     }
@@ -136,8 +230,7 @@ impl Serialize for Value {
                             }
                             Value::Empty => {}
                             _ => {
-                                error!("object contained an unexpected value: {:?}", value);
-                                process::exit(-1);
+                                fail(format!("object contained an unexpected value: {:?}", value));
                             }
                         }
                     }
@@ -150,8 +243,7 @@ impl Serialize for Value {
                 }
                 Value::Empty => serializer.serialize_map(Some(0))?.end(),
                 _ => {
-                    error!("object contained an unexpected value: {:?}", content);
-                    process::exit(-1);
+                    fail(format!("object contained an unexpected value: {:?}", content));
                 }
             },
             Value::List(list) => {
@@ -174,12 +266,33 @@ impl Serialize for Value {
             Value::U64(value) => serializer.serialize_u64(*value),
             Value::I64(value) => serializer.serialize_i64(*value),
             Value::F32(value) => serializer.serialize_f32(*value),
+            Value::F64(value) => serializer.serialize_f64(*value),
             Value::Bool(value) => serializer.serialize_bool(*value),
             Value::Empty => serializer.serialize_unit(),
         }
     }
 }
 
+/** JSON input, numbers become integers when they fit and floats otherwise */
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Empty,
+            serde_json::Value::Bool(value) => Value::Bool(value),
+            serde_json::Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                (Some(value), _) => Value::U64(value),
+                (_, Some(value)) => Value::I64(value),
+                _ => Value::F64(number.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(text) => Value::Str(text),
+            serde_json::Value::Array(values) => Value::List(values.into_iter().map(Value::from).collect()),
+            serde_json::Value::Object(entries) => Value::Object(Box::new(Value::List(entries.into_iter()
+                .map(|(key, value)| Value::KeyValue(Box::new(Value::Str(key)), Box::new(Value::from(value))))
+                .collect()))),
+        }
+    }
+}
+
 impl PartialEq<Value> for Value {
     fn eq(&self, other: &Value) -> bool {
         match (self, other) {
@@ -191,6 +304,9 @@ impl PartialEq<Value> for Value {
             (Value::U64(lhs), Value::U64(rhs)) => lhs == rhs,
             (Value::I64(lhs), Value::I64(rhs)) => lhs == rhs,
             (Value::F32(lhs), Value::F32(rhs)) => lhs == rhs,
+            (Value::F64(lhs), Value::F64(rhs)) => lhs == rhs,
+            (Value::F32(lhs), Value::F64(rhs)) => *lhs as f64 == *rhs,
+            (Value::F64(lhs), Value::F32(rhs)) => *lhs == *rhs as f64,
             (Value::List(lhs), Value::List(rhs)) => lhs == rhs,
             (Value::Iterator(lhs), Value::Iterator(rhs)) => lhs == rhs,
             (Value::KeyValue(lhs_lhs, lhs_rhs), Value::KeyValue(rhs_lhs, rhs_rhs)) => {
@@ -216,6 +332,10 @@ impl PartialOrd<Value> for Value {
             (Value::U64(lhs), Value::U64(rhs)) => lhs.partial_cmp(rhs),
             (Value::I64(lhs), Value::I64(rhs)) => lhs.partial_cmp(rhs),
             (Value::F32(lhs), Value::F32(rhs)) => lhs.partial_cmp(rhs),
+            (Value::F64(lhs), Value::F64(rhs)) => lhs.partial_cmp(rhs),
+            (Value::F32(lhs), Value::F64(rhs)) => (*lhs as f64).partial_cmp(rhs),
+            (Value::F64(lhs), Value::F32(rhs)) => lhs.partial_cmp(&(*rhs as f64)),
+            (Value::Str(lhs), Value::Str(rhs)) => lhs.partial_cmp(rhs),
 
             (lhs, rhs) if lhs == rhs => Some(Ordering::Equal),
             _ => None
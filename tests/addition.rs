@@ -1,5 +1,5 @@
 mod common;
-use common::process;
+use common::{error, process};
 
 #[test]
 fn numbers() {
@@ -24,3 +24,14 @@ fn objects() {
     let result = process("{foo: 0, bar: 1} + {foo: 1, baz: 1}");
     assert_eq!(result, vec![r#"{"bar":1,"foo":1,"baz":1}"#]);
 }
+
+#[test]
+fn integers_and_floats() {
+    assert_eq!(process("5 / 2 | . + 1, 1 + ."), vec!["3.5", "3.5"]);
+}
+
+#[test]
+fn overflow() {
+    assert_eq!(process("9223372036854775807 + 1"), vec!["9223372036854775808"]);
+    assert_eq!(error("18446744073709551615 + 1"), "Integer overflow: 18446744073709551615 + 1");
+}
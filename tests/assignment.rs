@@ -0,0 +1,62 @@
+mod common;
+use common::process;
+
+#[test]
+fn update() {
+    let result = process("{level: 2, id: 1} | .level |= . * 2");
+    assert_eq!(result, vec![r#"{"level":4,"id":1}"#]);
+}
+
+#[test]
+fn update_iterator() {
+    let result = process("[1, 2, 3] | .[] |= . + 1");
+    assert_eq!(result, vec!["[2,3,4]"]);
+}
+
+#[test]
+fn add_to_each() {
+    let result = process(r#"{stats: [{id: "a"}, {id: "b"}]} | .stats[] += {source: "mod"}"#);
+    assert_eq!(result, vec![r#"{"stats":[{"id":"a","source":"mod"},{"id":"b","source":"mod"}]}"#]);
+}
+
+#[test]
+fn subtract() {
+    let result = process("{a: 10} | .a -= 3");
+    assert_eq!(result, vec![r#"{"a":7}"#]);
+}
+
+#[test]
+fn set_relative_to_input() {
+    let result = process("{a: 1, b: 5} | .a = .b");
+    assert_eq!(result, vec![r#"{"a":5,"b":5}"#]);
+}
+
+#[test]
+fn set_missing_path() {
+    let result = process("{} | .a.b = 1");
+    assert_eq!(result, vec![r#"{"a":{"b":1}}"#]);
+
+    let result = process("[0] | .[2] = 1");
+    assert_eq!(result, vec!["[0,null,1]"]);
+}
+
+#[test]
+fn alternative() {
+    let result = process("{a: false, b: 2} | .a //= 1 | .b //= 1");
+    assert_eq!(result, vec![r#"{"a":1,"b":2}"#]);
+}
+
+#[test]
+fn stream() {
+    let result = process("[{a: 1}, {a: 2}][] | .a |= . * 10");
+    assert_eq!(result, vec![r#"{"a":10}"#, r#"{"a":20}"#]);
+}
+
+#[test]
+fn update_selected() {
+    let result = process("{a: [1, 2, 3]} | (.a[] | select(. > 1)) |= . * 10");
+    assert_eq!(result, vec![r#"{"a":[1,20,30]}"#]);
+
+    let result = process("{mods: [{level: 1, id: 1}, {level: 5, id: 2}]} | (.mods[] | select(.level > 2) | .id) = 0");
+    assert_eq!(result, vec![r#"{"mods":[{"level":1,"id":1},{"level":5,"id":0}]}"#]);
+}
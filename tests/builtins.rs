@@ -0,0 +1,102 @@
+mod common;
+use common::process;
+
+#[test]
+fn add() {
+    let result = process("[1, 2, 3] | add");
+    assert_eq!(result, vec!["6"]);
+
+    let result = process(r#"[{ring: 1000}, {amulet: 500}] | add"#);
+    assert_eq!(result, vec![r#"{"ring":1000,"amulet":500}"#]);
+
+    let result = process("[] | add");
+    assert_eq!(result, vec!["null"]);
+}
+
+#[test]
+fn any_all() {
+    let result = process("[false, true] | any, all");
+    assert_eq!(result, vec!["true", "false"]);
+
+    let result = process("[1, 2, 3] | any(. > 2), all(. > 2)");
+    assert_eq!(result, vec!["true", "false"]);
+}
+
+#[test]
+fn flatten() {
+    let result = process("[1, [2, [3]]] | flatten");
+    assert_eq!(result, vec!["[1,2,3]"]);
+
+    let result = process("[1, [2, [3]]] | flatten(1)");
+    assert_eq!(result, vec!["[1,2,[3]]"]);
+}
+
+#[test]
+fn range() {
+    let result = process("[range(4)]");
+    assert_eq!(result, vec!["[0,1,2,3]"]);
+
+    let result = process("[range(2; 10; 3)]");
+    assert_eq!(result, vec!["[2,5,8]"]);
+
+    let result = process("[range(3; 0; -1)]");
+    assert_eq!(result, vec!["[3,2,1]"]);
+}
+
+#[test]
+fn min_max() {
+    let result = process("[5, 2, 7] | min, max");
+    assert_eq!(result, vec!["2", "7"]);
+
+    let result = process(r#"["b", "a"] | min"#);
+    assert_eq!(result, vec![r#""a""#]);
+}
+
+#[test]
+fn indices() {
+    let result = process(r#""a,b, cd, efg" | indices(", ")"#);
+    assert_eq!(result, vec!["[3,7]"]);
+
+    let result = process("[0, 1, 2, 1, 3, 1, 2] | indices(1)");
+    assert_eq!(result, vec!["[1,3,5]"]);
+
+    let result = process("[0, 1, 2, 1, 3, 1, 2] | indices([1, 2])");
+    assert_eq!(result, vec!["[1,5]"]);
+}
+
+#[test]
+fn limit() {
+    let result = process("[0, 1, 2, 3] | [limit(2; .[])]");
+    assert_eq!(result, vec!["[0,1]"]);
+
+    let result = process("[0, 1, 2, 3] | [limit(2; .[] | select(. > 0))]");
+    assert_eq!(result, vec!["[1,2]"]);
+
+    // a list is one output, not one per element
+    let result = process("[limit(2; [1, 2, 3][] | [., .])]");
+    assert_eq!(result, vec!["[[1,1],[2,2]]"]);
+}
+
+#[test]
+fn first_last() {
+    let result = process("[4, 5, 6] | first, last");
+    assert_eq!(result, vec!["4", "6"]);
+
+    let result = process("[4, 5, 6] | first(.[] | select(. > 4)), last(.[])");
+    assert_eq!(result, vec!["5", "6"]);
+
+    let result = process("first([[1, 2], [3]][] | [.[0]]), first(range(5) | select(. > 2))");
+    assert_eq!(result, vec!["[1]", "3"]);
+}
+
+#[test]
+fn until() {
+    let result = process("1 | until(. > 100; . * 2)");
+    assert_eq!(result, vec!["128"]);
+}
+
+#[test]
+fn while_loop() {
+    let result = process("[1 | while(. < 20; . * 3)]");
+    assert_eq!(result, vec!["[1,3,9]"]);
+}
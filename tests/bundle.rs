@@ -0,0 +1,81 @@
+mod common;
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use poe_bundle::BundleReaderRead;
+use poe_query_lib::bundle;
+
+use common::{process_reader as process, Game};
+
+struct Bundles(HashMap<&'static str, Vec<u8>>);
+
+impl BundleReaderRead for Bundles {
+    fn size_of(&self, file: &str) -> Option<usize> {
+        self.0.get(file).map(Vec::len)
+    }
+
+    fn write_into(&self, file: &str, dst: &mut impl Write) -> Result<usize, io::Error> {
+        let bytes = self.bytes(file)?;
+        dst.write_all(&bytes)?;
+        Ok(bytes.len())
+    }
+
+    fn bytes(&self, file: &str) -> Result<Vec<u8>, io::Error> {
+        self.0.get(file).cloned().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in the bundles"))
+    }
+}
+
+fn matching(patterns: &[&str]) -> Vec<String> {
+    let paths = [
+        "data/mods.datc64",
+        "data/french/mods.datc64",
+        "metadata/items/currency/abstractcurrency.it",
+        "metadata/items/item.it",
+        "metadata/items/item.ot",
+        "metadata/statdescriptions/stat_descriptions.txt",
+    ].map(String::from);
+    let patterns: Vec<String> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+    bundle::matching(&paths, &patterns).into_iter().map(str::to_string).collect()
+}
+
+#[test]
+fn globs() {
+    assert_eq!(matching(&["data/*.datc64"]), vec!["data/mods.datc64"]);
+    assert_eq!(matching(&["data/**/mods.datc64"]), vec!["data/french/mods.datc64", "data/mods.datc64"]);
+    assert_eq!(matching(&["Metadata/Items/**.it"]), vec!["metadata/items/currency/abstractcurrency.it", "metadata/items/item.it"]);
+    assert_eq!(matching(&["metadata/items/item.?t", "**/stat_*.txt"]), vec![
+        "metadata/items/item.it",
+        "metadata/items/item.ot",
+        "metadata/statdescriptions/stat_descriptions.txt",
+    ]);
+    assert_eq!(matching(&[]).len(), 6);
+}
+
+#[test]
+fn extract() {
+    let bundles = Bundles(HashMap::from([("metadata/items/item.ot", b"version 2".to_vec()), ("data/mods.datc64", vec![0, 1, 2])]));
+    let output = Game::new("");
+    bundle::extract(&bundles, &["metadata/items/item.ot", "data/mods.datc64"], &output.join("out")).unwrap();
+    assert_eq!(std::fs::read(output.join("out/metadata/items/item.ot")).unwrap(), b"version 2");
+    assert_eq!(std::fs::read(output.join("out/data/mods.datc64")).unwrap(), [0, 1, 2]);
+
+    let error = bundle::extract(&bundles, &["missing.txt"], &output.join("out")).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    assert!(error.to_string().starts_with("missing.txt: "));
+}
+
+#[test]
+fn read_file() {
+    let text: Vec<u8> = [0xFF, 0xFE].into_iter().chain("Välkommen".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+    let game = Game::new("")
+        .write("Metadata/Welcome.txt", text)
+        .write("Metadata/Item.ot", "version 2")
+        .write("Art/Icon.bin", [0x00, 0xFF, 0x10]);
+    let reader = game.reader("English");
+    assert_eq!(process(&reader, r#""Metadata/Welcome.txt" | read_file"#), r#""Välkommen""#);
+    assert_eq!(process(&reader, r#""metadata/item.ot" | read_file"#), r#""version 2""#);
+    assert_eq!(process(&reader, r#""Art/Icon.bin" | read_file"#), "[0,255,16]");
+    assert_eq!(process(&reader, r#""Art/Icon.bin" | read_file | @base64"#), r#""AP8Q""#);
+    assert_eq!(process(&reader, r#""Art/Missing.bin" | read_file"#), "null");
+}
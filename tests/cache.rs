@@ -0,0 +1,87 @@
+mod common;
+
+use std::path::Path;
+
+use poe_query_lib::cache::{self, CachedBundles};
+use poe_query_lib::dat::DatReader;
+
+use common::{process_reader as process, strings, Game};
+
+// the index is not a real one, the tests fail if the bundles are indexed instead of read from the cache
+fn install() -> Game {
+    Game::new("type Tags {\n  Id: string @unique @localized\n}\n").write("install/Bundles2/_.index.bin", b"index")
+}
+
+fn fill(cache: &Path, install: &Path, files: &[(&str, Vec<u8>)]) {
+    let directory = cache.join(cache::version(install).unwrap());
+    std::fs::create_dir_all(&directory).unwrap();
+    let paths: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+    std::fs::write(directory.join("paths"), paths.join("\n")).unwrap();
+    for (path, bytes) in files {
+        let file = directory.join("files").join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, bytes).unwrap();
+    }
+}
+
+#[test]
+fn cached_tables() {
+    let game = install();
+    let (install, cache) = (game.join("install"), game.join("cache"));
+    fill(&cache, &install, &[("data/french/tags.datc64", strings(&["défaut"])), ("data/tags.datc64", strings(&["default"]))]);
+
+    let cached = CachedBundles::open(&install, &cache).unwrap();
+    let reader = DatReader::from_cache("French", &cached, &game.schema());
+    assert_eq!(reader.languages(), ["English", "French"]);
+    assert_eq!(process(&reader, "Tags[0].Id"), r#""défaut""#);
+    assert_eq!(process(&reader, r#"localized(Tags[0].Id)"#), r#"{"English":"default","French":"défaut"}"#);
+}
+
+#[test]
+fn patched() {
+    let game = install();
+    let (install, cache) = (game.join("install"), game.join("cache"));
+    let version = cache::version(&install).unwrap();
+    fill(&cache, &install, &[("data/tags.datc64", strings(&["default"]))]);
+    std::fs::create_dir_all(cache.join("unrelated")).unwrap();
+
+    std::fs::write(install.join("Bundles2/_.index.bin"), b"patched index").unwrap();
+    assert_ne!(cache::version(&install).unwrap(), version);
+    fill(&cache, &install, &[("data/tags.datc64", strings(&["patched"]))]);
+
+    let cached = CachedBundles::open(&install, &cache).unwrap();
+    let reader = DatReader::from_cache("English", &cached, &game.schema());
+    assert_eq!(process(&reader, "Tags[0].Id"), r#""patched""#);
+    assert!(!cache.join(version).exists());
+    assert!(cache.join("unrelated").exists());
+}
+
+#[test]
+fn shared_by_installs() {
+    let game = install().write("other/Bundles2/_.index.bin", b"other index");
+    let (install, other, cache) = (game.join("install"), game.join("other"), game.join("cache"));
+    fill(&cache, &install, &[("data/tags.datc64", strings(&["default"]))]);
+    fill(&cache, &other, &[("data/tags.datc64", strings(&["other"]))]);
+    assert_ne!(cache::version(&install).unwrap(), cache::version(&other).unwrap());
+
+    let cached = CachedBundles::open(&install, &cache).unwrap();
+    let reader = DatReader::from_cache("English", &cached, &game.schema());
+    assert_eq!(process(&reader, "Tags[0].Id"), r#""default""#);
+    let cached = CachedBundles::open(&other, &cache).unwrap();
+    let reader = DatReader::from_cache("English", &cached, &game.schema());
+    assert_eq!(process(&reader, "Tags[0].Id"), r#""other""#);
+    assert!(cache.join(cache::version(&install).unwrap()).exists());
+}
+
+#[test]
+fn other_files() {
+    let game = install();
+    let (install, cache) = (game.join("install"), game.join("cache"));
+    fill(&cache, &install, &[("data/tags.datc64", strings(&["default"])), ("metadata/items/item.ot", b"version 2".to_vec())]);
+
+    let cached = CachedBundles::open(&install, &cache).unwrap();
+    assert_eq!(cached.paths(), ["data/tags.datc64", "metadata/items/item.ot"]);
+    let reader = DatReader::from_cache("English", &cached, &game.schema());
+    assert_eq!(process(&reader, r#""Metadata/Items/Item.ot" | read_file"#), r#""version 2""#);
+    assert_eq!(process(&reader, r#""metadata/items/missing.ot" | read_file"#), "null");
+}
@@ -1,5 +1,12 @@
+// every test includes this module, most only use a part of it
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use poe_query_lib::{query, query::Term};
-use poe_query_lib::traversal::{QueryProcessor, StaticContext, value::Value};
+use poe_query_lib::dat::DatReader;
+use poe_query_lib::traversal::{QueryProcessor, SharedCache, StaticContext, value::Value};
 
 pub fn process(input: &str) -> Vec<String> {
     println!("Input: {}", input);
@@ -19,7 +26,13 @@ pub fn process(input: &str) -> Vec<String> {
     }
 }
 
-pub fn print_terms(terms: &Vec<Term>, indentation: u8) {
+/** The error message of a query that fails */
+pub fn error(input: &str) -> String {
+    let terms = query::parse_query(input).unwrap();
+    StaticContext::default().process_cached(&terms, &mut SharedCache::default()).unwrap_err()
+}
+
+pub fn print_terms(terms: &[Term], indentation: u8) {
     terms.iter().for_each(|term| {
         match term {
             Term::Calculate(lhs, op, rhs) => {
@@ -50,4 +63,103 @@ fn indent(levels: u8) {
     for _ in 0..levels {
         print!("\t");
     }
-}
\ No newline at end of file
+}
+
+/** Runs a query against the tables of `reader`, as compact JSON */
+pub fn process_reader(reader: &DatReader, input: &str) -> String {
+    let value = StaticContext::new(reader).process(&query::parse_query(input).unwrap());
+    serde_json::to_string(&value).unwrap()
+}
+
+/** A game folder in its own temporary directory, with the schema in `schema/` */
+pub struct Game {
+    pub path: PathBuf,
+}
+
+impl Game {
+    pub fn new(schema: &str) -> Game {
+        static GAMES: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("poe_query_test_{}_{}", std::process::id(), GAMES.fetch_add(1, Ordering::Relaxed));
+        let game = Game { path: std::env::temp_dir().join(name) };
+        let _ = std::fs::remove_dir_all(&game.path);
+        game.write("schema/_Core.gql", schema)
+    }
+
+    /** Writes a file below the game folder, creating its folders */
+    pub fn write(self, path: &str, bytes: impl AsRef<[u8]>) -> Game {
+        let path = self.path.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, bytes).unwrap();
+        self
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+
+    pub fn schema(&self) -> PathBuf {
+        self.path.join("schema")
+    }
+
+    /** Reads the extracted files of the folder like an install */
+    pub fn reader<'a>(&self, language: &'a str) -> DatReader<'a> {
+        DatReader::from_directory(language, &self.path, &self.schema())
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+pub fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).chain([0; 4]).collect()
+}
+
+/** Bytes of a datc64 table, strings and lists are written to the data section after the rows */
+pub struct Table {
+    count: u32,
+    rows: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table { count: 0, rows: Vec::new(), data: vec![0xBB; 8] }
+    }
+
+    /** Starts the next row, its fields follow in the order of the schema */
+    pub fn row(mut self) -> Table {
+        self.count += 1;
+        self
+    }
+
+    /** A fixed size field, e.g. a number or a foreign key */
+    pub fn value(mut self, bytes: impl AsRef<[u8]>) -> Table {
+        self.rows.extend(bytes.as_ref());
+        self
+    }
+
+    pub fn string(mut self, text: &str) -> Table {
+        self.rows.extend((self.data.len() as u64).to_le_bytes());
+        self.data.extend(utf16(text));
+        self
+    }
+
+    /** A list of `count` elements, their bytes are written to the data section */
+    pub fn list(mut self, count: usize, elements: impl AsRef<[u8]>) -> Table {
+        self.rows.extend([(count as u64).to_le_bytes(), (self.data.len() as u64).to_le_bytes()].concat());
+        self.data.extend(elements.as_ref());
+        self
+    }
+
+    pub fn bytes(self) -> Vec<u8> {
+        [self.count.to_le_bytes().to_vec(), self.rows, self.data].concat()
+    }
+}
+
+/** A table of one string column, e.g. `Tags` */
+pub fn strings(values: &[&str]) -> Vec<u8> {
+    values.iter().fold(Table::new(), |table, value| table.row().string(value)).bytes()
+}
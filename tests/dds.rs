@@ -0,0 +1,68 @@
+use std::io::Write;
+
+use poe_query_lib::dds;
+
+fn header(width: u32, height: u32, fourcc: &[u8; 4], flags: u32, bits: u32, masks: [u32; 4]) -> Vec<u8> {
+    let mut header = vec![0u8; 128];
+    header[..4].copy_from_slice(b"DDS ");
+    header[4..8].copy_from_slice(&124u32.to_le_bytes());
+    header[12..16].copy_from_slice(&height.to_le_bytes());
+    header[16..20].copy_from_slice(&width.to_le_bytes());
+    header[80..84].copy_from_slice(&flags.to_le_bytes());
+    header[84..88].copy_from_slice(fourcc);
+    header[88..92].copy_from_slice(&bits.to_le_bytes());
+    for (i, mask) in masks.iter().enumerate() {
+        header[92 + i * 4..96 + i * 4].copy_from_slice(&mask.to_le_bytes());
+    }
+    header
+}
+
+// a 4x4 texture in a single block that is red everywhere
+fn bc1() -> Vec<u8> {
+    let mut bytes = header(4, 4, b"DXT1", 0x4, 0, [0; 4]);
+    bytes.extend([0x00, 0xF8, 0x00, 0x00, 0, 0, 0, 0]);
+    bytes
+}
+
+fn brotli(bytes: &[u8]) -> Vec<u8> {
+    let mut compressed = (bytes.len() as u32).to_le_bytes().to_vec();
+    let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+    writer.write_all(bytes).unwrap();
+    drop(writer);
+    compressed
+}
+
+#[test]
+fn formats() {
+    let image = dds::decode(&bc1()).unwrap();
+    assert_eq!((image.width, image.height), (4, 4));
+    assert_eq!(image.rgba, [255, 0, 0, 255].repeat(16));
+
+    let mut bgra = header(2, 1, &[0; 4], 0x41, 32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000]);
+    bgra.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(dds::decode(&bgra).unwrap().rgba, vec![3, 2, 1, 4, 7, 6, 5, 8]);
+
+    assert_eq!(dds::decode(&brotli(&bc1())).unwrap().rgba, dds::decode(&bc1()).unwrap().rgba);
+    assert!(dds::decode(&header(4, 4, b"DXT9", 0x4, 0, [0; 4])).is_err());
+    assert!(dds::decode(b"not a texture").is_err());
+}
+
+#[test]
+fn references_and_png() {
+    let read = |path: &str| match path {
+        "art/icon.dds" => Some(b"*Art/Textures/Icon.dds".to_vec()),
+        "art/textures/icon.dds" => Some(brotli(&bc1())),
+        "art/loop.dds" => Some(b"*art/loop.dds".to_vec()),
+        _ => None,
+    };
+    let image = dds::read(&read, "art/icon.dds").unwrap();
+    assert!(dds::read(&read, "art/loop.dds").is_err());
+    assert!(dds::read(&read, "art/missing.dds").is_err());
+
+    let png = dds::png(&image).unwrap();
+    let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!((info.width, info.height), (4, 4));
+    assert_eq!(pixels, image.rgba);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use poe_query_lib::diff;
+use poe_query_lib::output;
+use poe_query_lib::output::OutputOptions;
+
+use common::{Game, Table};
+
+const SCHEMA: &str = "type Things {\n  Id: string @unique\n  Level: i32\n}\n\ntype Other {\n  Level: i32\n}\n";
+
+// rows of an Id and a Level, and a table of only levels
+fn extracted(rows: &[(&str, i32)], other: &[i32]) -> Game {
+    let things = rows.iter().fold(Table::new(), |table, (id, level)| table.row().string(id).value(level.to_le_bytes()));
+    let other = other.iter().fold(Table::new(), |table, level| table.row().value(level.to_le_bytes()));
+    Game::new(SCHEMA)
+        .write("Data/Things.datc64", things.bytes())
+        .write("Data/Other.datc64", other.bytes())
+}
+
+fn json(old: &Game, new: &Game, tables: &[&str]) -> String {
+    let (old, new) = (old.reader("English"), new.reader("English"));
+    let mut out = Vec::new();
+    output::write(&mut out, &diff::diff(&old, &new, tables), &OutputOptions { indent: None, ..OutputOptions::default() }).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn rows_by_unique_key() {
+    let old = extracted(&[("a", 1), ("b", 2), ("c", 3)], &[1]);
+    let new = extracted(&[("c", 3), ("a", 5), ("d", 4)], &[1]);
+    assert_eq!(json(&old, &new, &["Things", "Other"]), concat!(
+        r#"{"table":"Things","change":"changed","key":"a","fields":{"Level":{"old":1,"new":5}}}"#, "\n",
+        r#"{"table":"Things","change":"added","key":"d","row":{"Id":"d","Level":4}}"#, "\n",
+        r#"{"table":"Things","change":"removed","key":"b","row":{"Id":"b","Level":2}}"#, "\n",
+    ));
+}
+
+#[test]
+fn rows_by_index() {
+    let old = extracted(&[("a", 1)], &[1, 2]);
+    let new = extracted(&[("a", 1)], &[1, 3, 4]);
+    assert_eq!(json(&old, &new, &["Things", "Other"]), concat!(
+        r#"{"table":"Other","change":"changed","key":1,"fields":{"Level":{"old":2,"new":3}}}"#, "\n",
+        r#"{"table":"Other","change":"added","key":2,"row":{"Level":4}}"#, "\n",
+    ));
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use arrow_array::{Array, Int32Array, Int64Array, ListArray, StringArray, UInt64Array};
+use arrow_ipc::reader::FileReader;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rusqlite::Connection;
+
+use poe_query_lib::dat::DatStoreImpl;
+use poe_query_lib::dat::file::DatFile;
+use poe_query_lib::dat::specification::{EnumSpec, FieldSpec, FileSpec};
+use poe_query_lib::export;
+use poe_query_lib::export::arrow::Columnar;
+use poe_query_lib::query;
+use poe_query_lib::traversal::{QueryProcessor, StaticContext};
+
+/** A single table `Things` with a self referencing foreign key and a list of foreign keys */
+struct Store {
+    spec: FileSpec,
+}
+
+impl Store {
+    fn new() -> Self {
+        let field = |name: &str, field_type: &str, file_name: Option<&str>, field_size, field_offset| FieldSpec {
+            field_name: name.to_string(),
+            field_type: field_type.to_string(),
+            file_name: file_name.map(str::to_string),
+            file_reference_key: None,
+            enum_name: None,
+            field_size,
+            field_offset,
+            unique: false,
+            localized: false,
+        };
+        Store {
+            spec: FileSpec {
+                file_name: String::from("Things"),
+                file_fields: vec![
+                    field("Id", "ref|string", None, 8, 0),
+                    field("Level", "i32", None, 4, 8),
+                    field("Parent", "u64", Some("Things"), 8, 12),
+                    field("Children", "list|u64", Some("Things"), 16, 20),
+                ],
+            },
+        }
+    }
+
+    fn bytes() -> Vec<u8> {
+        let row = |id: u64, level: i32, parent: u64, children: u64| [
+            &id.to_le_bytes()[..],
+            &level.to_le_bytes(),
+            &parent.to_le_bytes(),
+            &children.to_le_bytes(),
+            &16u64.to_le_bytes(),
+        ].concat();
+
+        [
+            &2u32.to_le_bytes()[..],
+            &row(8, 1, 0xFEFEFEFEFEFEFEFE, 0),
+            &row(12, 2, 0, 1),
+            &[0xBB; 8],
+            &[b'a', 0, 0, 0, b'b', 0, 0, 0],
+            &0u64.to_le_bytes(),
+        ].concat()
+    }
+}
+
+impl<'a> DatStoreImpl<'a> for Store {
+    fn file_by_filename(&self, filename: &str) -> Option<DatFile> {
+        DatFile::from_bytes(filename.to_string(), Store::bytes()).ok()
+    }
+
+    fn spec(&self, path: &str) -> Option<&FileSpec> {
+        Some(&self.spec).filter(|spec| spec.file_name == path)
+    }
+
+    fn spec_by_export(&self, export: &str) -> Option<&FileSpec> {
+        self.spec(export)
+    }
+
+    fn exports(&self) -> HashSet<&str> {
+        HashSet::from([self.spec.file_name.as_str()])
+    }
+
+    fn enum_name(&self, _: &str) -> Option<&EnumSpec> {
+        None
+    }
+}
+
+#[test]
+fn sqlite_foreign_keys() {
+    let path = std::env::temp_dir().join("poe_query_export_test.sqlite");
+    let _ = std::fs::remove_file(&path);
+    export::sqlite::export(&Store::new(), &path, &["Things"]).unwrap();
+
+    let connection = Connection::open(&path).unwrap();
+    let (child, parent): (String, String) = connection.query_row(
+        "SELECT t.Id, p.Id FROM Things t JOIN Things p ON t.Parent = p._index", [], |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap();
+    assert_eq!((child.as_str(), parent.as_str()), ("b", "a"));
+
+    let (level, children): (i64, String) = connection.query_row(
+        "SELECT Level, Children FROM Things WHERE Id = 'b'", [], |row| Ok((row.get(0)?, row.get(1)?)),
+    ).unwrap();
+    assert_eq!((level, children.as_str()), (2, "[0]"));
+
+    let joined: String = connection.query_row(
+        "SELECT c.Id FROM Things_Children tc JOIN Things c ON tc.value = c._index WHERE tc._index = 1", [], |row| row.get(0),
+    ).unwrap();
+    assert_eq!(joined, "a");
+
+    let schema: String = connection.query_row("SELECT sql FROM sqlite_master WHERE name = 'Things'", [], |row| row.get(0)).unwrap();
+    assert!(schema.contains(r#""Parent" INTEGER REFERENCES "Things"("_index")"#), "{}", schema);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn arrow_table_keeps_nulls() {
+    let directory = std::env::temp_dir().join("poe_query_export_test_arrow");
+    export::arrow::export(&Store::new(), &directory, &["Things"], Columnar::ArrowIpc).unwrap();
+
+    let file = std::fs::File::open(directory.join("Things.arrow")).unwrap();
+    let batch = FileReader::try_new(file, None).unwrap().next().unwrap().unwrap();
+    let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+
+    let ids = column("Id");
+    let ids = ids.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!((ids.value(0), ids.value(1)), ("a", "b"));
+    let levels = column("Level");
+    assert_eq!(levels.as_any().downcast_ref::<Int32Array>().unwrap().values(), &[1, 2]);
+    let parents = column("Parent");
+    let parents = parents.as_any().downcast_ref::<UInt64Array>().unwrap();
+    assert!(parents.is_null(0));
+    assert_eq!(parents.value(1), 0);
+    let children = column("Children");
+    let children = children.as_any().downcast_ref::<ListArray>().unwrap();
+    assert_eq!(children.value(0).len(), 0);
+    assert_eq!(children.value(1).as_any().downcast_ref::<UInt64Array>().unwrap().values(), &[0]);
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn parquet_query_result() {
+    let terms = query::parse_query("[1, 2] | .[] as $x | {id: $x, name: \"thing\"}").unwrap();
+    let result = StaticContext::default().process(&terms);
+    let path = std::env::temp_dir().join("poe_query_export_test.parquet");
+    export::arrow::write_values(&mut std::fs::File::create(&path).unwrap(), &result, Columnar::Parquet).unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap().build().unwrap();
+    let batch = reader.into_iter().next().unwrap().unwrap();
+    assert_eq!(batch.num_rows(), 2);
+    let ids = batch.column_by_name("id").unwrap();
+    assert_eq!(ids.as_any().downcast_ref::<Int64Array>().unwrap().values(), &[1, 2]);
+    let names = batch.column_by_name("name").unwrap();
+    assert_eq!(names.as_any().downcast_ref::<StringArray>().unwrap().value(1), "thing");
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use poe_query_lib::dat::DatStoreImpl;
+use poe_query_lib::dat::file::FileBytes;
+
+use common::{process_reader as process, strings, Game, Table};
+
+const SCHEMA: &str = r#"type Tags {
+  Id: string @unique
+}
+
+type BaseItemTypes {
+  Id: string @unique
+  TagsKeys: [Tags]
+}
+"#;
+
+fn install() -> Game {
+    let bases = Table::new().row()
+        .string("Ring1")
+        .list(2, [0u64.to_le_bytes(), 1u64.to_le_bytes()].concat());
+    Game::new(SCHEMA)
+        .write("Data/Tags.datc64", strings(&["default", "ring"]))
+        .write("Data/BaseItemTypes.datc64", bases.bytes())
+}
+
+#[test]
+fn shared_between_queries_and_threads() {
+    let game = install();
+    let reader = game.reader("English");
+
+    let first = reader.file_by_filename("Tags").unwrap();
+    let second = reader.file_by_filename("Tags").unwrap();
+    assert_eq!(first.bytes.as_ptr(), second.bytes.as_ptr());
+
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| scope.spawn(|| process(&reader, "BaseItemTypes[0].TagsKeys | map(.Id)")))
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), r#"["default","ring"]"#);
+        }
+    });
+    assert_eq!(reader.file_by_filename("Tags").unwrap().bytes.as_ptr(), first.bytes.as_ptr());
+}
+
+#[test]
+fn mapped_from_disk() {
+    let game = install();
+    let cache = game.join("cache");
+    let reader = game.reader("English").with_file_cache(&cache);
+    assert!(matches!(reader.file_by_filename("Tags").unwrap().bytes, FileBytes::Memory(_)));
+    assert!(cache.join("data/tags.datc64").exists());
+
+    // a new reader maps the cached table, without the game file it is not read from the install
+    std::fs::remove_file(game.join("Data/Tags.datc64")).unwrap();
+    let reader = game.reader("English").with_file_cache(&cache);
+    assert!(matches!(reader.file_by_filename("Tags").unwrap().bytes, FileBytes::Mapped(_)));
+    assert_eq!(process(&reader, "Tags | map(.Id)"), r#"["default","ring"]"#);
+}
@@ -0,0 +1,56 @@
+mod common;
+use common::process;
+
+#[test]
+fn running_total() {
+    let result = process("[1, 2, 3, 4] | [foreach .[] as $item (0; . + $item)]");
+    assert_eq!(result, vec!["[1,3,6,10]"]);
+}
+
+#[test]
+fn extract() {
+    let result = process("[1, 2, 3] | [foreach .[] as $item (0; . + $item; [$item, .])]");
+    assert_eq!(result, vec!["[[1,1],[2,3],[3,6]]"]);
+}
+
+#[test]
+fn stream() {
+    let result = process("foreach range(1; 3) as $item (10; . - $item)");
+    assert_eq!(result, vec!["9", "7"]);
+}
+
+#[test]
+fn label_break() {
+    let result = process("[label $out | 1, 2, break $out, 3]");
+    assert_eq!(result, vec!["[1,2]"]);
+}
+
+#[test]
+fn break_foreach() {
+    let result = process("[1, 2, 3, 4] | [label $out | foreach .[] as $item (0; . + $item; select(. > 5) | ., break $out)]");
+    assert_eq!(result, vec!["[6]"]);
+}
+
+#[test]
+fn break_limit() {
+    let result = process("[label $out | foreach range(10) as $i (0; . + $i; ., (select(. >= 3) | break $out))]");
+    assert_eq!(result, vec!["[0,1,3]"]);
+}
+
+#[test]
+fn break_through_pipe() {
+    let result = process("[label $f | range(5) | ., (select(. == 2) | break $f)]");
+    assert_eq!(result, vec!["[0,1,2]"]);
+
+    let result = process("[label $f | [1, 2, 3][] | select(. > 1) | ., break $f]");
+    assert_eq!(result, vec!["[2]"]);
+}
+
+#[test]
+fn map_select() {
+    let result = process("[1, 2, 3] | map(select(. > 1))");
+    assert_eq!(result, vec!["[2,3]"]);
+
+    let result = process("[1, 2] | map(., . * 10)");
+    assert_eq!(result, vec!["[1,10,2,20]"]);
+}
@@ -0,0 +1,44 @@
+mod common;
+use common::process;
+
+#[test]
+fn csv() {
+    let result = process(r#"[({"a": 1} | @json), 2, null, true] | @csv"#);
+    assert_eq!(result, vec![r#""\"{\"\"a\"\":1}\",2,,true""#]);
+}
+
+#[test]
+fn tsv() {
+    let result = process(r#"["a", 2, null] | @tsv"#);
+    assert_eq!(result, vec![r#""a\t2\t""#]);
+}
+
+#[test]
+fn html() {
+    let result = process(r#""<a>&" | @html"#);
+    assert_eq!(result, vec![r#""&lt;a&gt;&amp;""#]);
+}
+
+#[test]
+fn sh() {
+    let result = process(r#"["ab", 1] | @sh"#);
+    assert_eq!(result, vec![r#""'ab' 1""#]);
+}
+
+#[test]
+fn base64() {
+    let result = process(r#""hello" | @base64, (@base64 | @base64d)"#);
+    assert_eq!(result, vec![r#""aGVsbG8=""#, r#""hello""#]);
+}
+
+#[test]
+fn uri() {
+    let result = process(r#""a b/ä" | @uri"#);
+    assert_eq!(result, vec![r#""a%20b%2F%C3%A4""#]);
+}
+
+#[test]
+fn json() {
+    let result = process(r#"[1, 2] | .[] | @json"#);
+    assert_eq!(result, vec![r#""1""#, r#""2""#]);
+}
@@ -0,0 +1,27 @@
+mod common;
+use common::{process, Game};
+
+use poe_query_lib::query;
+
+#[test]
+fn comments() {
+    let result = process("# leading comment\n[1, 2] # trailing comment\n| length # last line without newline");
+    assert_eq!(result, vec!["2"]);
+}
+
+#[test]
+fn include() {
+    let directory = Game::new("")
+        .write("lib/fields.pql", "{id: .Id} | include \"level\";")
+        .write("lib/level.pql", ". + {level: 1} # relative to fields.pql")
+        .write("lib/cycle.pql", "include \"cycle\";");
+
+    let source = query::expand_includes(r#"{Id: "Strength1"} | include "lib/fields"; | .id"#, &directory.path).unwrap();
+    assert_eq!(process(&source), vec![r#""Strength1""#]);
+
+    let source = query::expand_includes("# include \"ignored\"; in a comment\n'include \"ignored\";'", &directory.path).unwrap();
+    assert_eq!(process(&source), vec![r#""include \"ignored\";""#]);
+
+    let error = query::expand_includes(r#"include "lib/cycle";"#, &directory.path).unwrap_err();
+    assert!(error.contains("includes itself"), "{}", error);
+}
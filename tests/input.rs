@@ -0,0 +1,58 @@
+mod common;
+
+use serde_json::json;
+
+use poe_query_lib::query;
+use poe_query_lib::traversal::{QueryProcessor, StaticContext};
+use poe_query_lib::traversal::value::Value;
+
+use common::{process_reader, strings, Game};
+
+fn process_input(input: &str, json: serde_json::Value) -> String {
+    let terms = query::parse_query(input).unwrap();
+    let value = StaticContext::default().process_input(&terms, Value::from(json));
+    serde_json::to_string(&value).unwrap()
+}
+
+#[test]
+fn identity_is_input() {
+    let input = json!({"Level": 1, "Id": "Strength1", "Tags": ["str", "default"]});
+    assert_eq!(process_input(".", input.clone()), r#"{"Level":1,"Id":"Strength1","Tags":["str","default"]}"#);
+    assert_eq!(process_input(".Tags[1]", input.clone()), r#""default""#);
+    assert_eq!(process_input(".Level + 1", input), "2");
+}
+
+#[test]
+fn keys_before_tables() {
+    let game = Game::new("type Tags {\n  Id: string @unique\n}\n").write("Data/Tags.datc64", strings(&["default"]));
+    let reader = game.reader("English");
+    assert_eq!(process_reader(&reader, ".Tags[0].Id"), r#""default""#);
+    assert_eq!(process_reader(&reader, "{Tags: 1} | .Tags[0].Id"), r#""default""#);
+
+    let terms = query::parse_query(".Tags[0]").unwrap();
+    let value = StaticContext::new(&reader).process_input(&terms, Value::from(json!({"Tags": ["str"]})));
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""str""#);
+}
+
+#[test]
+fn numbers() {
+    assert_eq!(Value::from(json!(-1)), Value::I64(-1));
+    assert_eq!(Value::from(json!(u64::MAX)), Value::U64(u64::MAX));
+    assert_eq!(Value::from(json!(1.5)), Value::F64(1.5));
+    assert_eq!(Value::from(json!(null)), Value::Empty);
+}
+
+#[test]
+fn float_precision() {
+    assert_eq!(process_input(".", json!([1.2345678901234567, 1e40])), "[1.2345678901234567,1e40]");
+    assert_eq!(process_input(".[0] * 2", json!([0.1])), "0.2");
+    assert_eq!(process_input(".[0] / 2 > .[1]", json!([1, 0.1])), "true");
+}
+
+#[test]
+fn lookups() {
+    let terms = query::parse_query(r#"{a: .Mods[0].Id} | reduce .Stats[] as $s (0; . + 1)"#).unwrap();
+    assert_eq!(query::lookups(&terms), vec!["Mods"]);
+    let terms = query::parse_query(r#".Mods[0] as $m | .Stats, [.Tags[] | .Id] | .Level"#).unwrap();
+    assert_eq!(query::lookups(&terms), vec!["Mods", "Stats", "Tags"]);
+}
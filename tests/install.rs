@@ -0,0 +1,62 @@
+mod common;
+use common::Game;
+
+use std::path::PathBuf;
+
+use poe_query_lib::config::Config;
+use poe_query_lib::install;
+
+#[test]
+fn steam_libraries() {
+    let vdf = r#""libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/user/.local/share/Steam"
+		"label"		""
+	}
+	"1"
+	{
+		"path"		"D:\\SteamLibrary"
+	}
+}"#;
+    assert_eq!(install::steam_libraries(vdf), vec![PathBuf::from("/home/user/.local/share/Steam"), PathBuf::from("D:\\SteamLibrary")]);
+
+    let old = r#""LibraryFolders"
+{
+	"TimeNextStatsReport"		"1600000000"
+	"1"		"/mnt/games/SteamLibrary"
+}"#;
+    assert_eq!(install::steam_libraries(old), vec![PathBuf::from("/mnt/games/SteamLibrary")]);
+}
+
+#[test]
+fn steam_library_install() {
+    let home = Game::new("").write("games/steamapps/common/Path of Exile 2/Bundles2/_.index.bin", []);
+    let library = home.join("games");
+    let vdf = format!("\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}", library.display());
+    let home = home.write(".local/share/Steam/steamapps/libraryfolders.vdf", vdf);
+
+    let found = install::candidates(Some(&home.path)).into_iter().find(|path| install::is_install(path));
+    assert_eq!(found, Some(library.join("steamapps/common/Path of Exile 2")));
+}
+
+#[test]
+fn mac_standalone_install() {
+    let home = Game::new("").write("Library/Caches/com.GGG.PathOfExile/Rockets/Content.ggpk", []);
+
+    let found = install::candidates(Some(&home.path)).into_iter().find(|path| install::is_install(path));
+    assert_eq!(found, Some(home.join("Library/Caches/com.GGG.PathOfExile/Rockets")));
+}
+
+#[test]
+fn config() {
+    let directory = Game::new("");
+    let path = directory.join("config.toml");
+    assert_eq!(Config::read(&path), Ok(Config::default()));
+
+    let config = Config { language: Some(String::from("French")), ..Config::default() };
+    config.write(&path).unwrap();
+    let merged = Config::read(&path).unwrap().merge(Config { schema_path: Some(PathBuf::from("/schema")), ..Config::default() });
+    assert_eq!(merged, Config { install_path: None, language: Some(String::from("French")), schema_path: Some(PathBuf::from("/schema")), cache_path: None });
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use poe_query_lib::dat;
+use poe_query_lib::query;
+
+use common::{process_reader, Game, Table};
+
+const SCHEMA: &str = "type Things {\n  Id: string @unique\n  Name: string @localized\n}\n";
+
+// the French file has other ids too, only the @localized columns are read from it
+fn things(rows: &[(&str, &str)]) -> Vec<u8> {
+    rows.iter().fold(Table::new(), |table, (id, name)| table.row().string(id).string(name)).bytes()
+}
+
+fn install() -> Game {
+    Game::new(SCHEMA)
+        .write("Data/Things.datc64", things(&[("apple", "Apple"), ("pear", "Pear")]))
+        .write("Data/French/Things.datc64", things(&[("pomme", "Pomme"), ("poire", "Poire")]))
+}
+
+#[test]
+fn languages() {
+    let paths = [
+        "data/mods.datc64",
+        "data/stats.datc64",
+        "data/french/mods.datc64",
+        "data/simplified chinese/mods.datc64",
+        "data/german/stats.datc64",
+        "data/german/mods.datc64",
+        "data/balance/unrelated.datc64",
+        "art/french/mods.datc64",
+    ].map(String::from);
+    assert_eq!(dat::languages(&paths), vec!["English", "French", "German", "Simplified Chinese"]);
+}
+
+#[test]
+fn pipe_into_localized() {
+    assert_eq!(query::parse_query(".Mods[0].Name | localized"), query::parse_query("localized(.Mods[0].Name)"));
+    assert_eq!(query::parse_query(".Mods[0] | .Name | localized | length"), query::parse_query("localized(.Mods[0] | .Name) | length"));
+    assert_eq!(query::parse_query("[.Mods[0].Name | localized]"), query::parse_query("[localized(.Mods[0].Name)]"));
+}
+
+#[test]
+fn localized_columns() {
+    let game = install();
+    let reader = game.reader("English");
+    assert_eq!(process_reader(&reader, "localized(.Things[1].Name)"), r#"{"English":"Pear","French":"Poire"}"#);
+    assert_eq!(process_reader(&reader, ".Things[1].Id | localized"), r#"{"English":"pear","French":"pear"}"#);
+}
+
+#[test]
+fn rows_decoded_before() {
+    let game = install();
+    let reader = game.reader("English");
+    assert_eq!(process_reader(&reader, ".Things[1] | localized(.Name)"), r#"{"English":"Pear","French":"Poire"}"#);
+    assert_eq!(
+        process_reader(&reader, "[.Things[] | {Id, name: (.Name | localized)}]"),
+        r#"[{"Id":"apple","name":{"English":"Apple","French":"Pomme"}},{"Id":"pear","name":{"English":"Pear","French":"Poire"}}]"#
+    );
+}
+
+#[test]
+fn rows_by_position() {
+    let game = Game::new(SCHEMA)
+        .write("Data/Things.datc64", things(&[("pear", "Pear"), ("pear", "Pear"), ("apple", "Apple")]))
+        .write("Data/French/Things.datc64", things(&[("poire", "Poire"), ("poire", "Poire mûre"), ("pomme", "Pomme")]));
+    let reader = game.reader("English");
+    // the same content in two rows still reads each row's own translation
+    assert_eq!(process_reader(&reader, ".Things[1] | localized(.Name)"), r#"{"English":"Pear","French":"Poire mûre"}"#);
+    assert_eq!(process_reader(&reader, ".Things[-2] | localized(.Name)"), r#"{"English":"Pear","French":"Poire mûre"}"#);
+    assert_eq!(process_reader(&reader, ".Things[1:] | localized(map(.Name))"), r#"{"English":["Pear","Apple"],"French":["Poire mûre","Pomme"]}"#);
+    assert_eq!(
+        process_reader(&reader, r#".Things[] | select(.Id == "apple") | localized(.Name)"#),
+        r#"{"English":["Apple"],"French":["Pomme"]}"#
+    );
+}
+
+#[test]
+fn foreign_rows() {
+    let schema = format!("{}type Baskets {{\n  Thing: Things\n}}\n", SCHEMA);
+    let basket = Table::new().row().value(1u64.to_le_bytes()).value(0u64.to_le_bytes());
+    let game = Game::new(&schema)
+        .write("Data/Things.datc64", things(&[("pear", "Pear"), ("pear", "Pear")]))
+        .write("Data/French/Things.datc64", things(&[("poire", "Poire"), ("poire", "Poire mûre")]))
+        .write("Data/Baskets.datc64", basket.bytes());
+    let reader = game.reader("English");
+    assert_eq!(process_reader(&reader, ".Baskets[0].Thing | localized(.Name)"), r#"{"English":"Pear","French":"Poire mûre"}"#);
+}
@@ -1,5 +1,5 @@
 mod common;
-use common::process;
+use common::{error, process};
 
 #[test]
 fn map() {
@@ -37,3 +37,31 @@ fn reduce() {
 
     assert_eq!(result, vec![r#"{"ring":1000,"default":1000}"#]);
 }
+
+#[test]
+fn comparison() {
+    let result = process("[1, 2] | length > 1, .[0] == 1 + 1");
+    assert_eq!(result, vec!["true", "false"]);
+}
+
+#[test]
+fn group() {
+    let result = process("[(1, 2), 3]");
+    assert_eq!(result, vec!["[1,2,3]"]);
+
+    let result = process("{a: 1} | (.a | . + 1), .a");
+    assert_eq!(result, vec!["2", "1"]);
+}
+
+#[test]
+fn length_of_scalars() {
+    assert_eq!(process("null | length"), vec!["0"]);
+    assert_eq!(process("-3 | length"), vec!["3"]);
+    assert_eq!(error("true | length"), "Bool has no length");
+}
+
+#[test]
+fn keys_of_lists() {
+    assert_eq!(process("[5, 6] | keys"), vec!["[0,1]"]);
+    assert_eq!(error("null | keys"), "Empty has no keys");
+}
@@ -0,0 +1,45 @@
+mod common;
+use common::{error, process};
+
+#[test]
+fn numbers() {
+    let result = process("3 * 4");
+    assert_eq!(result[0], "12");
+}
+
+#[test]
+fn division() {
+    let result = process("10 / 5");
+    assert_eq!(result[0], "2");
+
+    let result = process("5 / 2");
+    assert_eq!(result[0], "2.5");
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(process("2 * 3 + 4"), vec!["10"]);
+    assert_eq!(process("4 + 2 * 3"), vec!["10"]);
+    assert_eq!(process("10 - 6 / 2"), vec!["7"]);
+    assert_eq!(process("(2 + 3) * 4"), vec!["20"]);
+}
+
+#[test]
+fn left_associative() {
+    assert_eq!(process("8 / 4 / 2"), vec!["1"]);
+    assert_eq!(process("10 - 3 - 2"), vec!["5"]);
+    assert_eq!(process("2 * 3 / 6"), vec!["1"]);
+    assert_eq!(process("1 < 2 == true"), vec!["true"]);
+    assert_eq!(process("1 + 1 == 2"), vec!["true"]);
+}
+
+#[test]
+fn integers_and_floats() {
+    assert_eq!(process("5 / 2 | . * 2, 2 * ."), vec!["5.0", "5.0"]);
+    assert_eq!(process(r#"{"a": (5 / 2)} | .a |= 2 * ."#), vec![r#"{"a":5.0}"#]);
+}
+
+#[test]
+fn overflow() {
+    assert_eq!(error("4294967296 * 4294967296"), "Integer overflow: 4294967296 * 4294967296");
+}
@@ -0,0 +1,75 @@
+use poe_query_lib::dat::object_template::ObjectTemplates;
+use poe_query_lib::output;
+use poe_query_lib::output::OutputOptions;
+use poe_query_lib::traversal::value::Value;
+
+const MONSTER: &str = r#"version 2
+abstract
+extends "nothing"
+
+Positioned
+{
+	team = 1
+}
+
+Stats
+{
+	level = 1
+	// overridden by children
+	radius = 0.5
+}
+"#;
+
+const ZOMBIE: &str = r#"version 2
+extends "Metadata/Monsters/Monster"
+
+Stats
+{
+	level = 12
+	tag = "undead"
+	tag = "melee"
+}
+
+Animated
+{
+	AnimatedObject = "Metadata/Monsters/Zombie/Zombie.ao"
+	passive
+}
+"#;
+
+fn read(path: &str) -> Option<Vec<u8>> {
+    let text = match path {
+        "metadata/monsters/monster.ot" => MONSTER,
+        "metadata/monsters/zombie/zombie.ot" => ZOMBIE,
+        "metadata/monsters/loop.ot" => "extends \"Metadata/Monsters/Loop\"",
+        _ => return None,
+    };
+    Some([0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect())
+}
+
+fn json(value: &Value) -> String {
+    let mut out = Vec::new();
+    output::write(&mut out, value, &OutputOptions { indent: None, ..OutputOptions::default() }).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn inheritance() {
+    let mut templates = ObjectTemplates::default();
+    let zombie = templates.resolve(&read, "Metadata/Monsters/Zombie/Zombie").unwrap();
+    assert_eq!(json(&zombie), concat!(
+        r#"{"Positioned":{"team":1},"#,
+        r#""Stats":{"level":12,"radius":0.5,"tag":["undead","melee"]},"#,
+        r#""Animated":{"AnimatedObject":"Metadata/Monsters/Zombie/Zombie.ao","passive":true}}"#,
+        "\n",
+    ));
+    let monster = templates.resolve(&read, "Metadata/Monsters/Monster.ot").unwrap();
+    assert_eq!(json(&monster), "{\"Positioned\":{\"team\":1},\"Stats\":{\"level\":1,\"radius\":0.5}}\n");
+}
+
+#[test]
+fn errors() {
+    let mut templates = ObjectTemplates::default();
+    assert!(templates.resolve(&read, "Metadata/Monsters/Missing").is_err());
+    assert_eq!(templates.resolve(&read, "Metadata/Monsters/Loop.ot"), Err(String::from("metadata/monsters/loop.ot extends itself")));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use poe_query_lib::output;
+use poe_query_lib::output::{Format, OutputOptions};
+use poe_query_lib::output::encoding::Encoding;
+use poe_query_lib::query;
+use poe_query_lib::traversal::{QueryProcessor, StaticContext};
+
+use common::{strings, Game};
+
+fn write(input: &str, options: OutputOptions) -> String {
+    write_with(StaticContext::default(), input, options)
+}
+
+fn write_with(context: StaticContext, input: &str, options: OutputOptions) -> String {
+    let terms = query::parse_query(input).unwrap();
+    let value = context.process(&terms);
+    let mut out = Vec::new();
+    output::write(&mut out, &value, &options).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn encode(input: &str, encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    let terms = query::parse_query(input).unwrap();
+    let value = StaticContext::default().process(&terms);
+    let mut out = Vec::new();
+    output::write(&mut out, &value, &OutputOptions { format: Format::Encoded(encoding), ..OutputOptions::default() }).map(|_| out)
+}
+
+#[test]
+fn pretty() {
+    let result = write(r#"{"a": [1, 2]}"#, OutputOptions::default());
+    assert_eq!(result, "{\n  \"a\": [\n    1,\n    2\n  ]\n}\n");
+}
+
+#[test]
+fn tab() {
+    let options = OutputOptions { indent: Some(String::from("\t")), ..OutputOptions::default() };
+    let result = write(r#"{"a": 1}"#, options);
+    assert_eq!(result, "{\n\t\"a\": 1\n}\n");
+}
+
+#[test]
+fn compact() {
+    let options = OutputOptions { indent: None, ..OutputOptions::default() };
+    let result = write(r#"{"a": 1}, [2]"#, options);
+    assert_eq!(result, "{\"a\":1}\n[2]\n");
+}
+
+#[test]
+fn raw_output() {
+    let options = OutputOptions { raw: true, ..OutputOptions::default() };
+    let result = write(r#""a", "b""#, options);
+    assert_eq!(result, "a\nb\n");
+}
+
+#[test]
+fn join_output() {
+    let options = OutputOptions { raw: true, join: true, ..OutputOptions::default() };
+    let result = write(r#""a", 1"#, options);
+    assert_eq!(result, "a1");
+}
+
+#[test]
+fn ndjson() {
+    let options = OutputOptions { indent: None, ndjson: true, ..OutputOptions::default() };
+    let result = write(r#"[{"a": 1}, {"a": 2}]"#, options);
+    assert_eq!(result, "{\"a\":1}\n{\"a\":2}\n");
+}
+
+#[test]
+fn csv_table() {
+    let options = OutputOptions { format: Format::Csv, ..OutputOptions::default() };
+    let result = write(r#"{"Id": "a", "Tags": [1, 2]}, {"Level": 3, "Id": "b"}"#, options);
+    assert_eq!(result, "\"Id\",\"Tags\",\"Level\"\n\"a\",\"[1,2]\",\n\"b\",,3\n");
+}
+
+#[test]
+fn yaml_documents() {
+    let result = encode(r#"{"a": 1}, [2, 3]"#, Encoding::Yaml).unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), "a: 1\n---\n- 2\n- 3\n");
+}
+
+#[test]
+fn toml_object() {
+    let result = encode(r#"{"a": 1, "b": "text"}"#, Encoding::Toml).unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), "a = 1\nb = \"text\"\n");
+}
+
+#[test]
+fn toml_rejects_scalars() {
+    let error = encode("1", Encoding::Toml).unwrap_err();
+    assert!(error.to_string().contains("TOML can only represent an object"), "{}", error);
+    let error = encode(r#"{"a": 1}, {"b": 2}"#, Encoding::Toml).unwrap_err();
+    assert!(error.to_string().contains("stream of 2 values"), "{}", error);
+}
+
+#[test]
+fn message_pack() {
+    let result = encode(r#"{"a": [1, 2]}"#, Encoding::MessagePack).unwrap();
+    let decoded: serde_json::Value = rmp_serde::from_slice(&result).unwrap();
+    assert_eq!(decoded, serde_json::json!({"a": [1, 2]}));
+}
+
+#[test]
+fn raw_stream() {
+    let game = Game::new("type Tags {\n  Id: string @unique\n}\n").write("data/tags.datc64", strings(&["default", "str"]));
+    let reader = game.reader("English");
+    let raw = || OutputOptions { raw: true, ..OutputOptions::default() };
+    assert_eq!(write_with(StaticContext::new(&reader), ".Tags[] | .Id", raw()), "default\nstr\n");
+    assert_eq!(write_with(StaticContext::new(&reader), ".Tags[].Id", raw()), "default\nstr\n");
+    let ndjson = OutputOptions { ndjson: true, ..raw() };
+    assert_eq!(write_with(StaticContext::new(&reader), ".Tags[].Id", ndjson), "default\nstr\n");
+
+    let input = serde_json::from_str::<serde_json::Value>(r#"[{"Id": "a"}, {"Id": "b"}]"#).unwrap();
+    let value = StaticContext::default().process_input(&query::parse_query(".[] | .Id").unwrap(), input.into());
+    let mut out = Vec::new();
+    output::write(&mut out, &value, &raw()).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "a\nb\n");
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use poe_query_lib::dat::passive_graph::{Connection, PassiveGraph, PASSIVE_SKILL_GRAPH};
+use poe_query_lib::export::passive_tree;
+use poe_query_lib::output;
+use poe_query_lib::output::OutputOptions;
+
+use common::{strings, Game, Table};
+
+const SCHEMA: &str = r#"type Stats {
+  Id: string @unique
+}
+
+type PassiveSkills {
+  Id: string @unique
+  Stats: [Stats]
+  Stat1Value: i32
+  PassiveSkillGraphId: u16 @unique
+  Name: string
+  IsNotable: bool
+}
+"#;
+
+// a root passive in the centre of the first group connected to one on the first orbit
+fn psg() -> Vec<u8> {
+    let mut bytes = vec![3, 0];
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(100u64.to_le_bytes());
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend([10f32.to_le_bytes(), (-20f32).to_le_bytes()].concat());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend([0; 5]);
+    bytes.extend(2u32.to_le_bytes());
+    for (id, orbit, index, connections) in [(100u32, 0u32, 0u32, vec![200u32]), (200, 1, 3, vec![])] {
+        bytes.extend([id, orbit, index, connections.len() as u32].iter().flat_map(|value| value.to_le_bytes()));
+        for connection in connections {
+            bytes.extend(connection.to_le_bytes());
+            bytes.extend((-1i32).to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn install() -> Game {
+    let passives = [("life", 0u64, 10i32, 100u16, "Life", 1u8), ("strength", 1, 5, 200, "Strength", 0)].iter()
+        .fold(Table::new(), |table, (id, stat, value, graph_id, name, notable)| table.row()
+            .string(id)
+            .list(1, stat.to_le_bytes())
+            .value(value.to_le_bytes())
+            .value(graph_id.to_le_bytes())
+            .string(name)
+            .value([*notable]));
+    Game::new(SCHEMA)
+        .write("Data/Stats.datc64", strings(&["base_maximum_life", "additional_strength"]))
+        .write("Data/PassiveSkills.datc64", passives.bytes())
+        .write(PASSIVE_SKILL_GRAPH, psg())
+        .write(
+            "Metadata/StatDescriptions/passive_skill_stat_descriptions.txt",
+            "description\n\t1 base_maximum_life\n\t1\n\t\t# \"{0:+d} to maximum Life\"\n",
+        )
+}
+
+#[test]
+fn graph_file() {
+    let graph = PassiveGraph::parse(&psg()).unwrap();
+    assert_eq!(graph.roots, vec![100]);
+    assert_eq!((graph.groups[0].x, graph.groups[0].y), (10.0, -20.0));
+    assert_eq!(graph.groups[0].passives[0].connections, vec![Connection { id: 200, curvature: -1 }]);
+    assert_eq!(graph.groups[0].passives[1].orbit_index, 3);
+
+    assert!(PassiveGraph::parse(&psg()[..40]).is_err());
+    assert!(PassiveGraph::parse(&[2, 0]).is_err());
+}
+
+#[test]
+fn tree_layout() {
+    let game = install();
+    let reader = game.reader("English");
+    let mut out = Vec::new();
+    output::write(&mut out, &passive_tree::tree(&reader, PASSIVE_SKILL_GRAPH).unwrap(), &OutputOptions { indent: None, ..OutputOptions::default() }).unwrap();
+    let tree: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(tree["nodes"]["root"]["out"], serde_json::json!(["100"]));
+    assert_eq!(tree["groups"]["1"], serde_json::json!({"x": 10.0, "y": -20.0, "orbits": [0, 1], "nodes": ["100", "200"]}));
+
+    let life = &tree["nodes"]["100"];
+    assert_eq!((&life["skill"], &life["name"], &life["isNotable"]), (&serde_json::json!(100), &serde_json::json!("Life"), &serde_json::json!(true)));
+    assert_eq!(life["stats"], serde_json::json!(["+10 to maximum Life"]));
+    assert_eq!((&life["out"], &life["in"]), (&serde_json::json!(["200"]), &serde_json::json!([])));
+
+    let strength = &tree["nodes"]["200"];
+    assert_eq!(strength.get("isNotable"), None);
+    assert_eq!((&strength["group"], &strength["orbit"], &strength["in"]), (&serde_json::json!(1), &serde_json::json!(1), &serde_json::json!(["100"])));
+    // half way around the first orbit is straight below the centre
+    assert_eq!((&tree["min_x"], &tree["max_y"]), (&serde_json::json!(10.0), &serde_json::json!(62.0)));
+}
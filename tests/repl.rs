@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use rustyline::completion::Completer;
+use rustyline::history::DefaultHistory;
+use rustyline::Context;
+
+use poe_query_lib::dat::DatStoreImpl;
+use poe_query_lib::dat::file::DatFile;
+use poe_query_lib::dat::specification::{EnumSpec, FieldSpec, FileSpec};
+use poe_query_lib::query;
+use poe_query_lib::repl::QueryHelper;
+use poe_query_lib::traversal::{QueryProcessor, SharedCache, StaticContext};
+
+/** `Mods` with a foreign key to `ModType`, only the specifications are needed for completion */
+struct Store {
+    specs: Vec<FileSpec>,
+}
+
+impl Store {
+    fn new() -> Self {
+        let field = |name: &str, file_name: Option<&str>| FieldSpec {
+            field_name: name.to_string(),
+            field_type: String::from("u64"),
+            file_name: file_name.map(str::to_string),
+            file_reference_key: None,
+            enum_name: None,
+            field_size: 8,
+            field_offset: 0,
+            unique: false,
+            localized: false,
+        };
+        let spec = |name: &str, fields| FileSpec { file_name: name.to_string(), file_fields: fields };
+        Store {
+            specs: vec![
+                spec("Mods", vec![field("Id", None), field("Level", None), field("ModTypeKey", Some("ModType"))]),
+                spec("ModType", vec![field("Name", None)]),
+            ],
+        }
+    }
+}
+
+impl<'a> DatStoreImpl<'a> for Store {
+    fn file_by_filename(&self, _: &str) -> Option<DatFile> {
+        None
+    }
+
+    fn spec(&self, path: &str) -> Option<&FileSpec> {
+        self.specs.iter().find(|spec| spec.file_name == path)
+    }
+
+    fn spec_by_export(&self, export: &str) -> Option<&FileSpec> {
+        self.spec(export)
+    }
+
+    fn exports(&self) -> HashSet<&str> {
+        self.specs.iter().map(|spec| spec.file_name.as_str()).collect()
+    }
+
+    fn enum_name(&self, _: &str) -> Option<&EnumSpec> {
+        None
+    }
+}
+
+fn complete(line: &str) -> (usize, Vec<String>) {
+    let history = DefaultHistory::new();
+    QueryHelper::new(&Store::new()).complete(line, line.len(), &Context::new(&history)).unwrap()
+}
+
+#[test]
+fn tables() {
+    assert_eq!(complete(".Mo"), (1, vec![String::from("ModType"), String::from("Mods")]));
+    assert_eq!(complete(":schema Mod"), (8, vec![String::from("ModType"), String::from("Mods")]));
+}
+
+#[test]
+fn fields() {
+    assert_eq!(complete(".Mods[0].L"), (9, vec![String::from("Level")]));
+    assert_eq!(complete(".Mods[0].ModTypeKey."), (20, vec![String::from("Name")]));
+    assert_eq!(complete(".Mods[] | select(.ModTypeKey.N"), (29, vec![String::from("Name")]));
+    assert_eq!(complete(".Mods[0].Level."), (15, vec![]));
+}
+
+#[test]
+fn commands() {
+    assert_eq!(complete(":sc"), (0, vec![String::from(":schema")]));
+}
+
+#[test]
+fn queries_share_cache() {
+    let context = StaticContext::default();
+    let terms = query::parse_query("label $out | 1, break $out, 2").unwrap();
+    let mut cache = SharedCache::default();
+    let first = context.process_cached(&terms, &mut cache).unwrap();
+    assert_eq!(first, context.process(&terms));
+    assert_eq!(context.process_cached(&terms, &mut cache), Ok(first));
+}
+
+#[test]
+fn errors_are_returned() {
+    let context = StaticContext::default();
+    let mut cache = SharedCache::default();
+    let failing = query::parse_query(r#"label $out | 1 + "a""#).unwrap();
+    assert_eq!(context.process_cached(&failing, &mut cache), Err(String::from("Operation not supported: Int + String")));
+    let terms = query::parse_query("label $out | 1, break $out, 2").unwrap();
+    assert_eq!(context.process_cached(&terms, &mut cache), Ok(context.process(&terms)));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{process_reader as process, strings, Game, Table};
+
+const SCHEMA: &str = r#"enum ModDomains @indexing(first: 1) {
+  ITEM
+  FLASK
+}
+
+enum ModGenerationType @indexing(first: 1) {
+  PREFIX
+  SUFFIX
+  UNIQUE
+}
+
+type Tags {
+  Id: string @unique
+}
+
+type BaseItemTypes {
+  Id: string @unique
+  InheritsFrom: string
+  TagsKeys: [Tags]
+  ModDomain: ModDomains
+}
+
+type Mods {
+  Id: string @unique
+  Domain: ModDomains
+  GenerationType: ModGenerationType
+  SpawnWeight_TagsKeys: [Tags]
+  SpawnWeight_Values: [i32]
+}
+"#;
+
+const TAGS: [&str; 4] = ["default", "ring", "amulet", "str_ring"];
+
+// Id, domain, generation type and the spawn weights by tag
+type Mod = (&'static str, u32, u32, &'static [(u64, i32)]);
+
+const MODS: [Mod; 6] = [
+    ("RingOnly", 1, 1, &[(1, 1000), (0, 0)]),
+    ("NotOnRings", 1, 2, &[(1, 0), (0, 500)]),
+    ("Anything", 1, 2, &[(2, 800), (0, 300)]),
+    ("StrengthRing", 1, 1, &[(3, 50)]),
+    ("Flask", 2, 1, &[(0, 1000)]),
+    ("Unique", 1, 3, &[(0, 1000)]),
+];
+
+fn install() -> Game {
+    let game = Game::new(SCHEMA)
+        .write("Metadata/Items/Item.ot", "version 2\n\nBase\n{\n\ttag = \"default\"\n}\n")
+        .write("Metadata/Items/Rings/AbstractRing.ot", "version 2\nextends \"Metadata/Items/Item\"\n\nBase\n{\n\ttag = \"ring\"\n}\n")
+        .write("Data/Tags.datc64", strings(&TAGS));
+
+    let base = Table::new().row()
+        .string("Metadata/Items/Rings/Ring1")
+        .string("Metadata/Items/Rings/AbstractRing")
+        .list(1, 3u64.to_le_bytes())
+        .value(1u32.to_le_bytes());
+    let game = game.write("Data/BaseItemTypes.datc64", base.bytes());
+
+    let mods = MODS.iter().fold(Table::new(), |table, (id, domain, generation, weights)| table.row()
+        .string(id)
+        .value(domain.to_le_bytes())
+        .value(generation.to_le_bytes())
+        .list(weights.len(), weights.iter().flat_map(|(tag, _)| tag.to_le_bytes()).collect::<Vec<_>>())
+        .list(weights.len(), weights.iter().flat_map(|(_, weight)| weight.to_le_bytes()).collect::<Vec<_>>()));
+    game.write("Data/Mods.datc64", mods.bytes())
+}
+
+#[test]
+fn first_matching_tag() {
+    let game = install();
+    let reader = game.reader("English");
+
+    // the ring tag comes from the object file the base inherits from and is checked before default
+    assert_eq!(
+        process(&reader, r#"rollable_mods("Metadata/Items/Rings/Ring1") | map([.Id, .Weight])"#),
+        r#"[["RingOnly",1000],["Anything",300],["StrengthRing",50]]"#
+    );
+    assert_eq!(process(&reader, r#"rollable_mods("Metadata/Items/Rings/Ring1"; ["unique"]) | map(.Id)"#), r#"["Unique"]"#);
+    assert_eq!(process(&reader, r#"rollable_mods("Metadata/Items/Rings/Missing")"#), "[]");
+    // the rows are Mods rows, foreign keys can be followed
+    assert_eq!(process(&reader, r#"rollable_mods("Metadata/Items/Rings/Ring1") | map(.SpawnWeight_TagsKeys[0].Id)"#), r#"["ring","amulet","str_ring"]"#);
+}
@@ -0,0 +1,83 @@
+use poe_query_lib::dat::stat_description::{decode, StatDescriptions};
+use poe_query_lib::query;
+
+const DESCRIPTIONS: &str = r#"no_description level
+description
+	1 base_maximum_life
+	2
+		1|# "{0:+d} to maximum [Life|Life]"
+		#|-1 "{0} to maximum [Life|Life]"
+	lang "German"
+	1
+		# "{0:+d} zu maximalem Leben"
+description
+	2 attack_minimum_added_physical_damage attack_maximum_added_physical_damage
+	1
+		# # "Adds {0} to {1} Physical Damage to Attacks"
+description
+	1 life_regeneration_rate_per_minute_%
+	1
+		# "Regenerate {0}% of Life per second" per_minute_to_per_second 1
+description
+	1 attack_speed_+%
+	2
+		1|# "{0}% increased Attack Speed"
+		#|-1 "{0}% reduced Attack Speed" negate 1
+"#;
+
+fn describe(language: &str, stats: &[(&str, i64, i64)]) -> Vec<String> {
+    let stats: Vec<(String, i64, i64)> = stats.iter().map(|(id, min, max)| (id.to_string(), *min, *max)).collect();
+    StatDescriptions::parse(DESCRIPTIONS, language).describe(&stats)
+}
+
+#[test]
+fn conditions_and_handlers() {
+    assert_eq!(describe("English", &[("base_maximum_life", 10, 15)]), vec!["+(10-15) to maximum Life"]);
+    assert_eq!(describe("English", &[("base_maximum_life", -5, -5)]), vec!["-5 to maximum Life"]);
+    assert_eq!(describe("English", &[("attack_speed_+%", -8, -8)]), vec!["8% reduced Attack Speed"]);
+    assert_eq!(describe("English", &[("attack_speed_+%", -20, -10)]), vec!["(10-20)% reduced Attack Speed"]);
+    assert_eq!(describe("English", &[("life_regeneration_rate_per_minute_%", 120, 120)]), vec!["Regenerate 2% of Life per second"]);
+    assert_eq!(describe("English", &[("level", 1, 1), ("base_maximum_life", 0, 0)]), Vec::<String>::new());
+}
+
+#[test]
+fn stats_share_a_line_in_file_order() {
+    let stats = [
+        ("attack_speed_+%", 5, 5),
+        ("attack_maximum_added_physical_damage", 4, 6),
+        ("attack_minimum_added_physical_damage", 1, 2),
+    ];
+    assert_eq!(describe("English", &stats), vec!["Adds (1-2) to (4-6) Physical Damage to Attacks", "5% increased Attack Speed"]);
+}
+
+#[test]
+fn languages() {
+    assert_eq!(describe("german", &[("base_maximum_life", 7, 7)]), vec!["+7 zu maximalem Leben"]);
+    // English is used when a description has no translation
+    assert_eq!(describe("German", &[("attack_speed_+%", 3, 3)]), vec!["3% increased Attack Speed"]);
+}
+
+#[test]
+fn includes() {
+    let utf16 = |text: &str| [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect::<Vec<u8>>();
+    assert_eq!(decode(&utf16(DESCRIPTIONS)), DESCRIPTIONS);
+
+    let read = |path: &str| match path {
+        "a.txt" => Some(utf16("include \"b.txt\"\ndescription\n\t1 level\n\t1\n\t\t# \"Level {0}\"")),
+        "b.txt" => Some(utf16("description\n\t1 level\n\t1\n\t\t# \"Replaced\"\ndescription\n\t1 quality\n\t1\n\t\t# \"Quality {0}\"")),
+        _ => None,
+    };
+    let descriptions = StatDescriptions::load(&read, "a.txt", "English").unwrap();
+    let stats = [(String::from("quality"), 20, 20), (String::from("level"), 3, 3)];
+    assert_eq!(descriptions.describe(&stats), vec!["Quality 20", "Level 3"]);
+    assert!(StatDescriptions::load(&read, "missing.txt", "English").is_err());
+}
+
+#[test]
+fn describe_mod() {
+    let expanded = query::parse_query(
+        "stat_text([.StatsKey1.Id, .StatsKey2.Id, .StatsKey3.Id, .StatsKey4.Id, .StatsKey5.Id, .StatsKey6.Id]; \
+        [[.Stat1Min, .Stat1Max], [.Stat2Min, .Stat2Max], [.Stat3Min, .Stat3Max], [.Stat4Min, .Stat4Max], [.Stat5Min, .Stat5Max], [.Stat6Min, .Stat6Max]])"
+    );
+    assert_eq!(query::parse_query(".Mods[0] | describe_mod").unwrap()[4..], expanded.unwrap()[..]);
+}
@@ -23,3 +23,9 @@ fn string_length() {
     let result = process("\"abcåäö\" | length");
     assert_eq!(result, vec!["6"]);
 }
+
+#[test]
+fn hash_in_string() {
+    let result = process(r#""a # b", 'c # d', " e""#);
+    assert_eq!(result, vec![r#""a # b""#, r#""c # d""#, r#"" e""#]);
+}
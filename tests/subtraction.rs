@@ -12,3 +12,14 @@ fn arrays() {
     let result = process(r#"["xml", "yaml", "json"] - ["xml", "yaml"]"#);
     assert_eq!(result, vec![r#"["json"]"#]);
 }
+
+#[test]
+fn below_zero() {
+    assert_eq!(process("5 - 10"), vec!["-5"]);
+    assert_eq!(process(r#"{"a": 5} | .a -= 10"#), vec![r#"{"a":-5}"#]);
+}
+
+#[test]
+fn integers_and_floats() {
+    assert_eq!(process("5 / 2 | . - 3, 3 - ."), vec!["-0.5", "0.5"]);
+}
@@ -0,0 +1,96 @@
+mod common;
+use common::process;
+
+use poe_query_lib::query;
+use poe_query_lib::traversal::{QueryProcessor, StaticContext};
+use poe_query_lib::traversal::value::Value;
+
+#[test]
+fn array_pattern() {
+    let result = process("[1, 2] | . as [$a, $b] | $a + $b");
+    assert_eq!(result, vec!["3"]);
+}
+
+#[test]
+fn object_pattern() {
+    let result = process(r#"{"Id": "Strength1", "Level": 1} | . as {Id: $id, Level: $lvl} | [$id, $lvl]"#);
+    assert_eq!(result, vec![r#"["Strength1",1]"#]);
+}
+
+#[test]
+fn nested_pattern() {
+    let result = process(r#"{"a": [1, {"b": 2}]} | . as {a: [$x, {$b}]} | [$x, $b]"#);
+    assert_eq!(result, vec!["[1,2]"]);
+}
+
+#[test]
+fn missing_values_are_null() {
+    let result = process("[3] | . as [$a, $b] | [$a, $b]");
+    assert_eq!(result, vec!["[3,null]"]);
+}
+
+#[test]
+fn alternative_pattern() {
+    let result = process(r#"{"a": 1} | . as [$x] ?// {a: $x} | $x"#);
+    assert_eq!(result, vec!["1"]);
+}
+
+#[test]
+fn alternative_after_error() {
+    let result = process(r#"[[3], [4]] | .[] as [$a] ?// $a | $a + [1]"#);
+    assert_eq!(result, vec!["[3,1]", "[4,1]"]);
+}
+
+#[test]
+fn reduce_pattern() {
+    let result = process(r#"[["ring", 1000], ["amulet", 500]] | reduce .[] as [$tag, $weight] ({}; . + {($tag): $weight})"#);
+    assert_eq!(result, vec![r#"{"ring":1000,"amulet":500}"#]);
+}
+
+#[test]
+fn body_keeps_input() {
+    let result = process(r#"{"a": 1} | .a as $x | ."#);
+    assert_eq!(result, vec![r#"{"a":1}"#]);
+}
+
+#[test]
+fn binds_each_output() {
+    let result = process("[1, 2] | [.[] as $x | $x * 10]");
+    assert_eq!(result, vec!["[10,20]"]);
+}
+
+#[test]
+fn lexical_scope() {
+    let result = process("1 as $x | [(2 as $x | $x), $x]");
+    assert_eq!(result, vec!["[2,1]"]);
+}
+
+#[test]
+fn nested_bindings() {
+    let result = process("[1, 2] | [.[] as $x | [3, 4] | .[] as $y | $x * $y]");
+    assert_eq!(result, vec!["[3,4,6,8]"]);
+}
+
+#[test]
+fn predefined_variables() {
+    let terms = query::parse_query("[$name, (2 as $name | $name)]").unwrap();
+    let context = StaticContext::default().with_variables(vec![(String::from("name"), Value::Str(String::from("Strength1")))]);
+    let value = serde_json::to_string(&context.process(&terms)).unwrap();
+    assert_eq!(value, r#"["Strength1",2]"#);
+}
+
+#[test]
+fn environment() {
+    std::env::set_var("POE_QUERY_TEST", "value");
+    let result = process("[$ENV.POE_QUERY_TEST, env.POE_QUERY_TEST]");
+    assert_eq!(result, vec![r#"["value","value"]"#]);
+}
+
+#[test]
+fn select_bound() {
+    let result = process("[1, 2, 3] | .[] as $x | select($x > 1) | $x");
+    assert_eq!(result, vec!["2", "3"]);
+
+    let result = process("[1, 2, 3] | .[] as $x | select($x > 2) | [$x, length]");
+    assert_eq!(result, vec!["[3,3]"]);
+}